@@ -1,11 +1,50 @@
+use std::any::Any;
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
-use serde::{de::DeserializeOwned, Serialize};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::util::app_data_dir;
+use crate::util::{app_data_dir, log_event};
+
+// 任何缓存结构体字段变化时递增，旧条目会被自动清除
+const SCHEMA_VERSION: u32 = 3;
+const CACHE_MAGIC: &[u8; 4] = b"MDUC";
+const HEADER_LEN: usize = 24;
+
+static MAX_CACHE_BYTES: AtomicU64 = AtomicU64::new(256 * 1024 * 1024);
+// 缓存总大小的累计值，UNKNOWN_BYTES 表示需要重新统计；每隔若干次写入重新统计一次以修正偏差
+const UNKNOWN_BYTES: u64 = u64::MAX;
+const RESCAN_EVERY_WRITES: u64 = 64;
+static CACHE_BYTES: AtomicU64 = AtomicU64::new(UNKNOWN_BYTES);
+static WRITES_SINCE_SCAN: AtomicU64 = AtomicU64::new(0);
+
+// 本次会话内已解码的大对象，按缓存文件名索引，避免重复反序列化
+type SessionEntry = Arc<dyn Any + Send + Sync>;
+static SESSION: Lazy<RwLock<HashMap<String, SessionEntry>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub const CATEGORIES: [&str; 7] = [
+    "cf-files",
+    "cf-mod",
+    "mr-versions",
+    "mr-brief",
+    "mc_versions",
+    "icons",
+    "mappings",
+];
+// 映射按版本不变且下载代价大，不计入大小上限，也不参与 LRU 淘汰，只能手动清除
+pub const UNLIMITED_CATEGORIES: [&str; 1] = ["mappings"];
+
+fn counts_toward_limit(category: &str) -> bool {
+    !UNLIMITED_CATEGORIES.contains(&category)
+}
 
 pub fn cache_dir() -> PathBuf {
     let base = app_data_dir().join("cache");
@@ -13,6 +52,12 @@ pub fn cache_dir() -> PathBuf {
     base
 }
 
+pub fn icons_dir() -> PathBuf {
+    let dir = app_data_dir().join("icons");
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
 pub fn cache_path(name: &str) -> PathBuf {
     cache_dir().join(name)
 }
@@ -40,24 +85,356 @@ pub fn now_millis() -> u64 {
         .unwrap_or(0)
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_response(resp: &reqwest::Response) -> Self {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        };
+        Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    pub fn apply(&self, rb: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let mut rb = rb;
+        if let Some(etag) = &self.etag {
+            rb = rb.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            rb = rb.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        rb
+    }
+}
+
+pub fn is_not_modified(resp: &reqwest::Response) -> bool {
+    resp.status() == reqwest::StatusCode::NOT_MODIFIED
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    // FNV-1a 64
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn encode_entry(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(CACHE_MAGIC);
+    out.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    out.extend_from_slice(&checksum(payload).to_le_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+fn header_schema(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != CACHE_MAGIC {
+        return None;
+    }
+    Some(u32::from_le_bytes(bytes[4..8].try_into().ok()?))
+}
+
+// 启动时只需判断版本，不读取整个文件
+fn read_header(path: &std::path::Path) -> Option<[u8; HEADER_LEN]> {
+    let mut header = [0u8; HEADER_LEN];
+    let mut f = fs::File::open(path).ok()?;
+    std::io::Read::read_exact(&mut f, &mut header).ok()?;
+    Some(header)
+}
+
+fn decode_entry(bytes: &[u8]) -> Result<&[u8]> {
+    match header_schema(bytes) {
+        None => return Err(anyhow!("missing cache header")),
+        Some(v) if v != SCHEMA_VERSION => {
+            return Err(anyhow!(
+                "cache schema {} does not match {}",
+                v,
+                SCHEMA_VERSION
+            ))
+        }
+        Some(_) => {}
+    }
+    let len = u64::from_le_bytes(bytes[8..16].try_into()?) as usize;
+    let sum = u64::from_le_bytes(bytes[16..24].try_into()?);
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() != len || checksum(payload) != sum {
+        return Err(anyhow!("cache checksum mismatch"));
+    }
+    Ok(payload)
+}
+
 pub fn read_bincode<T: DeserializeOwned>(name: &str) -> Result<T> {
     let path = cache_path(name);
-    let bytes = fs::read(path)?;
-    let value = bincode::deserialize::<T>(&bytes)?;
+    let bytes = fs::read(&path)?;
+    let value = decode_entry(&bytes)
+        .and_then(|payload| Ok(bincode::deserialize::<T>(payload)?))
+        .inspect_err(|e| {
+            log_event("warn", &format!("cache_purged {} {}", name, e));
+            let _ = fs::remove_file(&path);
+        })?;
+    touch(&path);
     Ok(value)
 }
 
 pub fn write_bincode<T: Serialize>(name: &str, value: &T) -> Result<()> {
     let path = cache_path(name);
-    let bytes = bincode::serialize(value)?;
-    fs::write(path, bytes)?;
+    let bytes = encode_entry(&bincode::serialize(value)?);
+    let tmp = cache_path(&format!("{}.tmp", name));
+    let old_size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    let new_size = bytes.len() as u64;
+    fs::write(&tmp, bytes)?;
+    if let Err(e) = fs::rename(&tmp, &path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    if category_of(name).map(counts_toward_limit).unwrap_or(true) {
+        record_write(old_size, new_size);
+    }
     Ok(())
 }
 
+fn record_write(old_size: u64, new_size: u64) {
+    let writes = WRITES_SINCE_SCAN.fetch_add(1, Ordering::Relaxed) + 1;
+    let total = CACHE_BYTES.load(Ordering::Relaxed);
+    if total == UNKNOWN_BYTES || writes >= RESCAN_EVERY_WRITES {
+        enforce_size_limit();
+        return;
+    }
+    let total = (total + new_size).saturating_sub(old_size);
+    CACHE_BYTES.store(total, Ordering::Relaxed);
+    if total > max_cache_bytes() {
+        enforce_size_limit();
+    }
+}
+
+pub fn session_get<T: Any + Send + Sync>(name: &str) -> Option<Arc<T>> {
+    let entry = SESSION.read().unwrap().get(name).cloned()?;
+    entry.downcast::<T>().ok()
+}
+
+pub fn session_put<T: Any + Send + Sync>(name: &str, value: Arc<T>) {
+    SESSION.write().unwrap().insert(name.to_string(), value);
+}
+
+// 先查内存，再查磁盘，命中磁盘时放入内存
+pub fn read_session_bincode<T: DeserializeOwned + Any + Send + Sync>(name: &str) -> Option<Arc<T>> {
+    if let Some(v) = session_get::<T>(name) {
+        return Some(v);
+    }
+    let value = Arc::new(read_bincode::<T>(name).ok()?);
+    session_put(name, value.clone());
+    Some(value)
+}
+
+pub fn write_session_bincode<T: Serialize + Any + Send + Sync>(
+    name: &str,
+    value: Arc<T>,
+) -> Result<()> {
+    session_put(name, value.clone());
+    write_bincode(name, value.as_ref())
+}
+
+fn session_remove_category(category: &str) {
+    SESSION
+        .write()
+        .unwrap()
+        .retain(|name, _| category_of(name) != Some(category));
+}
+
+pub fn purge_incompatible_entries() {
+    let rd = match fs::read_dir(cache_dir()) {
+        Ok(rd) => rd,
+        Err(_) => return,
+    };
+    for entry in rd.flatten() {
+        let path = entry.path();
+        let leftover_tmp = path.extension().map(|e| e == "tmp").unwrap_or(false);
+        let compatible = !leftover_tmp
+            && read_header(&path)
+                .and_then(|header| header_schema(&header))
+                .map(|v| v == SCHEMA_VERSION)
+                .unwrap_or(false);
+        if !compatible && fs::remove_file(&path).is_ok() {
+            log_event(
+                "info",
+                &format!("cache_purged_incompatible {}", path.to_string_lossy()),
+            );
+        }
+    }
+}
+
+// 用修改时间记录最近使用，供 LRU 淘汰
+fn touch(path: &std::path::Path) {
+    if let Ok(f) = fs::File::options().write(true).open(path) {
+        let _ = f.set_modified(SystemTime::now());
+    }
+}
+
+fn category_of(file_name: &str) -> Option<&'static str> {
+    CATEGORIES
+        .iter()
+        .copied()
+        .filter(|c| *c != "icons")
+        .find(|c| file_name.starts_with(c))
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    category: &'static str,
+    size: u64,
+    modified: SystemTime,
+}
+
+fn list_entries() -> Vec<CacheEntry> {
+    let mut out = Vec::new();
+    for (dir, is_icons) in [(cache_dir(), false), (icons_dir(), true)] {
+        let rd = match fs::read_dir(&dir) {
+            Ok(rd) => rd,
+            Err(_) => continue,
+        };
+        for entry in rd.flatten() {
+            let meta = match entry.metadata() {
+                Ok(m) if m.is_file() => m,
+                _ => continue,
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            let category = if is_icons {
+                Some("icons")
+            } else {
+                category_of(&name)
+            };
+            if let Some(category) = category {
+                out.push(CacheEntry {
+                    path: entry.path(),
+                    category,
+                    size: meta.len(),
+                    modified: meta.modified().unwrap_or(UNIX_EPOCH),
+                });
+            }
+        }
+    }
+    out
+}
+
+#[derive(Serialize, Debug)]
+pub struct CategoryStats {
+    pub name: String,
+    pub entries: usize,
+    pub bytes: u64,
+    pub counts_toward_limit: bool,
+}
+
+pub fn cache_stats() -> Vec<CategoryStats> {
+    let entries = list_entries();
+    CATEGORIES
+        .iter()
+        .map(|c| {
+            let items: Vec<&CacheEntry> = entries.iter().filter(|e| e.category == *c).collect();
+            CategoryStats {
+                name: c.to_string(),
+                entries: items.len(),
+                bytes: items.iter().map(|e| e.size).sum(),
+                counts_toward_limit: counts_toward_limit(c),
+            }
+        })
+        .collect()
+}
+
+pub fn max_cache_bytes() -> u64 {
+    MAX_CACHE_BYTES.load(Ordering::Relaxed)
+}
+
+pub fn set_max_cache_bytes(max_bytes: u64) {
+    MAX_CACHE_BYTES.store(max_bytes, Ordering::Relaxed);
+    enforce_size_limit();
+}
+
+pub fn enforce_size_limit() {
+    let max = max_cache_bytes();
+    let mut entries = list_entries();
+    entries.retain(|e| counts_toward_limit(e.category));
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    WRITES_SINCE_SCAN.store(0, Ordering::Relaxed);
+    if total > max {
+        entries.sort_by_key(|e| e.modified);
+        for e in entries {
+            if total <= max {
+                break;
+            }
+            if fs::remove_file(&e.path).is_ok() {
+                total = total.saturating_sub(e.size);
+                log_event(
+                    "info",
+                    &format!("cache_evicted {} {}", e.category, e.path.to_string_lossy()),
+                );
+            }
+        }
+    }
+    CACHE_BYTES.store(total, Ordering::Relaxed);
+}
+
+pub fn invalidate_category(category: &str) -> Result<usize> {
+    if !CATEGORIES.contains(&category) {
+        return Err(anyhow!("Unknown cache category: {}", category));
+    }
+    let mut removed = 0;
+    for e in list_entries()
+        .into_iter()
+        .filter(|e| e.category == category)
+    {
+        fs::remove_file(&e.path)?;
+        removed += 1;
+    }
+    session_remove_category(category);
+    CACHE_BYTES.store(UNKNOWN_BYTES, Ordering::Relaxed);
+    Ok(removed)
+}
+
+// 删除文件名以任一前缀开头的条目，连同内存中的会话条目
+pub fn invalidate_prefixes(prefixes: &[String]) -> Result<usize> {
+    let matches = |name: &str| prefixes.iter().any(|p| name.starts_with(p.as_str()));
+    let mut removed = 0;
+    for e in list_entries() {
+        let name = e
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if matches(&name) {
+            fs::remove_file(&e.path)?;
+            removed += 1;
+        }
+    }
+    SESSION.write().unwrap().retain(|name, _| !matches(name));
+    CACHE_BYTES.store(UNKNOWN_BYTES, Ordering::Relaxed);
+    Ok(removed)
+}
+
+pub fn offline_miss(what: &str) -> anyhow::Error {
+    anyhow!("Offline mode: {} was never cached", what)
+}
+
 pub fn clear_all_cache() -> Result<()> {
-    let dir = cache_dir();
-    if dir.exists() {
-        fs::remove_dir_all(&dir)?;
+    SESSION.write().unwrap().clear();
+    CACHE_BYTES.store(UNKNOWN_BYTES, Ordering::Relaxed);
+    for dir in [cache_dir(), icons_dir()] {
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
     }
     Ok(())
 }
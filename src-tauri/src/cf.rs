@@ -1,9 +1,11 @@
-use crate::cache::{now_millis, read_bincode, write_bincode};
-use crate::util::{log_event, shorten};
+use crate::cache::{
+    is_not_modified, now_millis, offline_miss, read_bincode, write_bincode, Validators,
+};
+use crate::util::{is_offline, log_event, shorten};
 use anyhow::{anyhow, Context};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 static VERSION_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"\d+(?:\.\d+)*(?:[-+][a-zA-Z0-9_.-]+)?").unwrap());
 
@@ -12,7 +14,7 @@ struct CfModResponse {
     data: CfModData,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CfLatestFileIndex {
     #[serde(rename = "gameVersion")]
     pub game_version: String,
@@ -26,7 +28,7 @@ pub struct CfLatestFileIndex {
     pub mod_loader: Option<u8>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct CfModData {
     id: u32,
     slug: String,
@@ -36,7 +38,7 @@ struct CfModData {
     latest_files_indexes: Vec<CfLatestFileIndex>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct CfLogo {
     #[serde(rename = "url")]
     url: String,
@@ -63,107 +65,106 @@ pub fn strip_jar_suffix(name: &str) -> String {
     }
 }
 
-pub async fn get_cf_mod_brief(
-    project_id: u32,
-    api_key: &str,
-) -> anyhow::Result<(String, Option<String>)> {
+#[derive(Serialize, Deserialize, Debug)]
+struct CfModCache {
+    data: CfModData,
+    fetched_at: u64,
+    validators: Validators,
+}
+
+fn cf_mod_cache_name(project_id: u32) -> String {
+    format!("cf-mod-{}.bin", project_id)
+}
+
+async fn fetch_mod_data(project_id: u32, api_key: &str) -> anyhow::Result<CfModData> {
+    let cached = read_bincode::<CfModCache>(&cf_mod_cache_name(project_id)).ok();
+    if is_offline() {
+        return cached
+            .map(|cache| cache.data)
+            .ok_or_else(|| offline_miss(&format!("CurseForge project {}", project_id)));
+    }
     let client = crate::util::http_client()?;
     let url = format!("https://api.curseforge.com/v1/mods/{}", project_id);
-    let resp = crate::util::send_with_retry(
-        client
-            .get(&url)
-            .header("x-api-key", api_key)
-            .header("Accept", "application/json"),
-        2,
-    )
-    .await
-    .context("Failed to fetch mod detail from CurseForge")?;
+    let mut rb = client
+        .get(&url)
+        .header("x-api-key", api_key)
+        .header("Accept", "application/json");
+    if let Some(cache) = &cached {
+        rb = cache.validators.apply(rb);
+    }
+    let resp = crate::util::send_with_retry(rb, 2)
+        .await
+        .context("Failed to fetch mod detail from CurseForge")?;
+    if is_not_modified(&resp) {
+        if let Some(mut cache) = cached {
+            log_event("info", &format!("cf_mod_not_modified {}", project_id));
+            cache.fetched_at = now_millis();
+            let _ = write_bincode(&cf_mod_cache_name(project_id), &cache);
+            return Ok(cache.data);
+        }
+    }
+    let validators = Validators::from_response(&resp);
     let status = resp.status();
     let body_text = resp
         .text()
         .await
         .context("Failed to read mod detail body")?;
     if !status.is_success() {
-        crate::util::log_event(
+        log_event(
             "error",
             &format!(
                 "CF detail status {} url {} body {}",
                 status,
                 url,
-                crate::util::shorten(&body_text, 400)
+                shorten(&body_text, 400)
             ),
         );
         return Err(anyhow!(format!(
             "CurseForge API Error (Mod Detail): {} body {}",
             status,
-            crate::util::shorten(&body_text, 400)
+            shorten(&body_text, 400)
         )));
     }
     let body: CfModResponse = serde_json::from_str(&body_text).map_err(|e| {
         anyhow!(format!(
             "CurseForge parse error: {} body {}",
             e,
-            crate::util::shorten(&body_text, 400)
+            shorten(&body_text, 400)
         ))
     })?;
-    let icon = body
-        .data
+    let cache = CfModCache {
+        data: body.data.clone(),
+        fetched_at: now_millis(),
+        validators,
+    };
+    let _ = write_bincode(&cf_mod_cache_name(project_id), &cache);
+    Ok(body.data)
+}
+
+pub async fn get_cf_mod_brief(
+    project_id: u32,
+    api_key: &str,
+) -> anyhow::Result<(String, Option<String>)> {
+    let data = fetch_mod_data(project_id, api_key).await?;
+    let icon = data
         .logo
         .thumbnail_url
         .clone()
-        .or(Some(body.data.logo.url.clone()));
+        .or(Some(data.logo.url.clone()));
     log_event(
         "info",
         &format!(
             "cf_mod_brief {} {}",
-            body.data.name,
+            data.name,
             icon.clone().unwrap_or_default()
         ),
     );
-    Ok((body.data.name, icon))
+    Ok((data.name, icon))
 }
 
 pub async fn get_project_meta(project_id: u32, api_key: &str) -> anyhow::Result<(String, u32)> {
-    let client = crate::util::http_client()?;
-    let url = format!("https://api.curseforge.com/v1/mods/{}", project_id);
-    let resp = crate::util::send_with_retry(
-        client
-            .get(&url)
-            .header("x-api-key", api_key)
-            .header("Accept", "application/json"),
-        2,
-    )
-    .await
-    .context("Failed to connect to CurseForge API")?;
-    let status = resp.status();
-    let body_text = resp
-        .text()
-        .await
-        .context("Failed to read CurseForge response body")?;
-    if !status.is_success() {
-        log_event(
-            "error",
-            &format!(
-                "CF meta status {} url {} body {}",
-                status,
-                url,
-                shorten(&body_text, 400)
-            ),
-        );
-        return Err(anyhow!(format!(
-            "CurseForge API Error: {} body {}",
-            status,
-            shorten(&body_text, 400)
-        )));
-    }
-    let body: CfModResponse = serde_json::from_str(&body_text).map_err(|e| {
-        anyhow!(format!(
-            "CurseForge parse error: {} body {}",
-            e,
-            shorten(&body_text, 400)
-        ))
-    })?;
-    Ok((body.data.slug, body.data.id))
+    let data = fetch_mod_data(project_id, api_key).await?;
+    Ok((data.slug, data.id))
 }
 
 pub async fn get_latest_cf_file(
@@ -171,64 +172,57 @@ pub async fn get_latest_cf_file(
     mc_version: &str,
     loader: &str,
     api_key: &str,
-) -> anyhow::Result<(Option<u32>, Option<String>, Option<u8>)> {
-    let client = crate::util::http_client()?;
-    let url = format!("https://api.curseforge.com/v1/mods/{}", project_id);
-    let resp = crate::util::send_with_retry(
-        client
-            .get(&url)
-            .header("x-api-key", api_key)
-            .header("Accept", "application/json"),
-        2,
-    )
-    .await
-    .context("Failed to fetch mod detail from CurseForge")?;
-    let status = resp.status();
-    let body_text = resp
-        .text()
-        .await
-        .context("Failed to read mod detail body")?;
-    if !status.is_success() {
-        log_event(
-            "error",
-            &format!(
-                "CF detail status {} url {} body {}",
-                status,
-                url,
-                shorten(&body_text, 400)
-            ),
-        );
-        return Err(anyhow!(format!(
-            "CurseForge API Error (Mod Detail): {} body {}",
-            status,
-            shorten(&body_text, 400)
-        )));
-    }
-    let body: CfModResponse = serde_json::from_str(&body_text).map_err(|e| {
-        anyhow!(format!(
-            "CurseForge parse error: {} body {}",
-            e,
-            shorten(&body_text, 400)
-        ))
-    })?;
+    max_release_level: u8,
+    fallback: bool,
+) -> anyhow::Result<(Option<u32>, Option<String>, Option<u8>, Option<String>)> {
+    let data = fetch_mod_data(project_id, api_key).await?;
     let target_loader = crate::util::loader_name_to_tag(&loader);
-    for release_type in [1u8, 2, 3] {
-        for idx in &body.data.latest_files_indexes {
+    let target_loader = target_loader.as_str();
+    let spec = crate::mojang::VersionSpec::parse(mc_version)?;
+    let found = |idx: &CfLatestFileIndex| {
+        let version = extract_version(&idx.filename).unwrap_or_else(|| idx.file_id.to_string());
+        Ok((
+            Some(idx.file_id),
+            Some(version),
+            Some(idx.release_type),
+            Some(idx.game_version.clone()),
+        ))
+    };
+    let candidates = |release_type: u8| {
+        data.latest_files_indexes.iter().filter(move |idx| {
             let tag = idx
                 .mod_loader
                 .map(|code| cf_mod_loader_to_tag(code))
                 .unwrap_or("Unknown");
-            if idx.release_type != release_type {
-                continue;
-            }
-            if idx.game_version == mc_version && tag == target_loader.as_str() {
-                let version =
-                    extract_version(&idx.filename).unwrap_or_else(|| idx.file_id.to_string());
-                return Ok((Some(idx.file_id), Some(version), Some(idx.release_type)));
+            idx.release_type == release_type && tag == target_loader
+        })
+    };
+    // 范围匹配时先取满足条件的最新 Minecraft 版本，同一版本再取最新的文件（与 Modrinth 一致）
+    for release_type in (1u8..=3).filter(|t| *t <= max_release_level) {
+        let best = candidates(release_type)
+            .filter(|idx| spec.matches(&idx.game_version))
+            .max_by(|a, b| {
+                crate::mojang::compare_mc_versions(&a.game_version, &b.game_version)
+                    .then(a.file_id.cmp(&b.file_id))
+            });
+        if let Some(idx) = best {
+            return found(idx);
+        }
+    }
+    if fallback && spec.is_exact() {
+        for release_type in (1u8..=3).filter(|t| *t <= max_release_level) {
+            let nearest = crate::mojang::nearest_compatible(
+                mc_version,
+                candidates(release_type).map(|idx| idx.game_version.as_str()),
+            );
+            if let Some(idx) =
+                nearest.and_then(|v| candidates(release_type).find(|i| i.game_version == v))
+            {
+                return found(idx);
             }
         }
     }
-    Ok((None, None, None))
+    Ok((None, None, None, None))
 }
 
 pub fn cf_mod_loader_to_tag(code: u8) -> &'static str {
@@ -247,46 +241,8 @@ pub async fn get_cf_latest_indexes(
     project_id: u32,
     api_key: &str,
 ) -> anyhow::Result<Vec<CfLatestFileIndex>> {
-    let client = crate::util::http_client()?;
-    let url = format!("https://api.curseforge.com/v1/mods/{}", project_id);
-    let resp = crate::util::send_with_retry(
-        client
-            .get(&url)
-            .header("x-api-key", api_key)
-            .header("Accept", "application/json"),
-        2,
-    )
-    .await
-    .context("Failed to fetch mod detail from CurseForge")?;
-    let status = resp.status();
-    let body_text = resp
-        .text()
-        .await
-        .context("Failed to read mod detail body")?;
-    if !status.is_success() {
-        log_event(
-            "error",
-            &format!(
-                "CF detail status {} url {} body {}",
-                status,
-                url,
-                shorten(&body_text, 400)
-            ),
-        );
-        return Err(anyhow!(format!(
-            "CurseForge API Error (Mod Detail): {} body {}",
-            status,
-            shorten(&body_text, 400)
-        )));
-    }
-    let body: CfModResponse = serde_json::from_str(&body_text).map_err(|e| {
-        anyhow!(format!(
-            "CurseForge parse error: {} body {}",
-            e,
-            shorten(&body_text, 400)
-        ))
-    })?;
-    Ok(body.data.latest_files_indexes)
+    let data = fetch_mod_data(project_id, api_key).await?;
+    Ok(data.latest_files_indexes)
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -314,11 +270,29 @@ struct CfFilesResponse {
 struct CfFilesCache {
     files: Vec<CfFileItem>,
     fetched_at: u64,
+    validators: Validators,
+}
+
+fn cf_files_cache_prefix(project_id: u32) -> String {
+    format!("cf-files-{}-", project_id)
 }
 
 fn cf_cache_name(project_id: u32, mc_version: &str, loader_code: u8) -> String {
     let v = crate::cache::safe_key_segment(mc_version);
-    format!("cf-files-{}-{}-{}.bin", project_id, v, loader_code)
+    format!(
+        "{}{}-{}.bin",
+        cf_files_cache_prefix(project_id),
+        v,
+        loader_code
+    )
+}
+
+// 某个项目的全部缓存条目（文件名前缀）
+pub fn project_cache_prefixes(project_id: u32) -> Vec<String> {
+    vec![
+        cf_mod_cache_name(project_id),
+        cf_files_cache_prefix(project_id),
+    ]
 }
 
 pub fn cf_mod_loader_code_from_name(name: &str) -> Option<u8> {
@@ -338,8 +312,19 @@ pub async fn get_cf_files_filtered(
     api_key: &str,
     use_cache: bool,
 ) -> anyhow::Result<Vec<CfFileItem>> {
-    let client = crate::util::http_client()?;
     const TTL_MS: u64 = 6 * 60 * 60 * 1000;
+    if is_offline() {
+        return read_bincode::<CfFilesCache>(&cf_cache_name(project_id, mc_version, loader_code))
+            .map(|cache| cache.files)
+            .map_err(|_| {
+                offline_miss(&format!(
+                    "CurseForge files of {} for MC {}",
+                    project_id, mc_version
+                ))
+            });
+    }
+    let client = crate::util::http_client()?;
+    let mut cached: Option<CfFilesCache> = None;
     if use_cache {
         if let Ok(cache) =
             read_bincode::<CfFilesCache>(&cf_cache_name(project_id, mc_version, loader_code))
@@ -348,26 +333,46 @@ pub async fn get_cf_files_filtered(
             if age <= TTL_MS {
                 return Ok(cache.files);
             }
+            cached = Some(cache);
         }
     }
     let page_size: u32 = 50;
     let mut index: u32 = 0;
     let mut all: Vec<CfFileItem> = Vec::new();
+    let mut validators = Validators::default();
     const MAX_FILES: usize = 500;
     loop {
         let url = format!(
             "https://api.curseforge.com/v1/mods/{}/files?gameVersion={}&modLoaderType={}&pageSize={}&index={}",
             project_id, mc_version, loader_code, page_size, index
         );
-        let resp = crate::util::send_with_retry(
-            client
-                .get(&url)
-                .header("x-api-key", api_key)
-                .header("Accept", "application/json"),
-            2,
-        )
-        .await
-        .context("Failed to fetch mod files from CurseForge")?;
+        let mut rb = client
+            .get(&url)
+            .header("x-api-key", api_key)
+            .header("Accept", "application/json");
+        // 只对第一页做条件请求，304 表示整个列表未变化
+        if index == 0 {
+            if let Some(cache) = &cached {
+                rb = cache.validators.apply(rb);
+            }
+        }
+        let resp = crate::util::send_with_retry(rb, 2)
+            .await
+            .context("Failed to fetch mod files from CurseForge")?;
+        if index == 0 && is_not_modified(&resp) {
+            if let Some(mut cache) = cached.take() {
+                log_event(
+                    "info",
+                    &format!("cf_files_not_modified {} {}", project_id, mc_version),
+                );
+                cache.fetched_at = now_millis();
+                let _ = write_bincode(&cf_cache_name(project_id, mc_version, loader_code), &cache);
+                return Ok(cache.files);
+            }
+        }
+        if index == 0 {
+            validators = Validators::from_response(&resp);
+        }
         let status = resp.status();
         let body_text = resp.text().await.context("Failed to read mod files body")?;
         if !status.is_success() {
@@ -404,6 +409,7 @@ pub async fn get_cf_files_filtered(
         let cache = CfFilesCache {
             files: all.clone(),
             fetched_at: now_millis(),
+            validators,
         };
         let _ = write_bincode(&cf_cache_name(project_id, mc_version, loader_code), &cache);
     }
@@ -1,29 +1,30 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[derive(Clone)]
-struct ClassMaps {
-    yarn_named_to_inter: HashMap<String, String>,
-    yarn_inter_to_named: HashMap<String, String>,
-    moj_inter_to_named: HashMap<String, String>,
-    moj_named_to_inter: HashMap<String, String>,
-}
-
-#[derive(Clone)]
-struct MemberMaps {
-    yarn_named_to_inter_by_class: HashMap<String, HashMap<String, String>>,
-    yarn_inter_to_named_by_class: HashMap<String, HashMap<String, String>>,
-    moj_inter_to_named_by_class: HashMap<String, HashMap<String, String>>,
-    moj_named_to_inter_by_class: HashMap<String, HashMap<String, String>>,
-}
-
-#[derive(Clone)]
-struct Maps {
-    class_maps: ClassMaps,
-    method_maps: MemberMaps,
-    field_maps: MemberMaps,
-}
+static MAVEN_VERSION_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<version>([^<]+)</version>").unwrap());
+static GRADLE_AW_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"accessWidenerPath\s*(?:=|\.set\()\s*file\(\s*["']([^"']+)["']"#).unwrap()
+});
+static TOML_AT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\[\[accessTransformers\]\]\s*file\s*=\s*["']([^"']+)["']"#).unwrap()
+});
+static MIXIN_IMPORT_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^(\s*import\s+)([\w.$]+)(\s*;)").unwrap());
+static MIXIN_ANNOTATION_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"@Mixin\s*\(([^)]*)\)").unwrap());
+static CLASS_LITERAL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"([\w.$]+)\.class\b").unwrap());
+static STRING_LITERAL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#""([^"]*)""#).unwrap());
+static ANNOTATION_START_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"@(\w+)\s*\(").unwrap());
+static MIXIN_METHOD_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\bmethod\s*=\s*(\{[^}]*\}|"[^"]*")"#).unwrap());
+static MIXIN_TARGET_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\btarget\s*=\s*"([^"]*)""#).unwrap());
 
 fn disclaimer_lines() -> Vec<String> {
     vec![
@@ -46,433 +47,1369 @@ async fn fetch_mappings(ns: &str, version: &str) -> anyhow::Result<Value> {
     Ok(v)
 }
 
-fn build_maps_from_yarn(v: &Value) -> (ClassMaps, MemberMaps, MemberMaps) {
-    let mut class_named_to_inter = HashMap::new();
-    let mut class_inter_to_named = HashMap::new();
-    let mut method_named_to_inter_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    let mut method_inter_to_named_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    let mut field_named_to_inter_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    let mut field_inter_to_named_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    if let Value::Object(map) = v {
-        for (inter, obj) in map {
-            let mut named_opt = None;
-            if let Value::Object(obj_map) = obj {
-                for (k, val) in obj_map {
-                    if k == "mappedName" {
-                        if let Some(named) = val.as_str() {
-                            named_opt = Some(named.to_string());
-                        }
-                        continue;
-                    }
-                    if let Some(named) = val.as_str() {
-                        method_named_to_inter_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(named.to_string(), k.clone());
-                        method_inter_to_named_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(k.clone(), named.to_string());
-                        field_named_to_inter_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(named.to_string(), k.clone());
-                        field_inter_to_named_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(k.clone(), named.to_string());
-                    }
-                }
-            }
-            if let Some(named) = named_opt {
-                class_named_to_inter.insert(named.clone(), inter.clone());
-                class_inter_to_named.insert(inter.clone(), named.clone());
-            }
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct NsMaps {
+    class_to_inter: HashMap<String, String>,
+    class_from_inter: HashMap<String, String>,
+    // 方法以 名称+intermediary 描述符 为键，避免重载互相覆盖
+    method_to_inter: HashMap<String, HashMap<String, String>>,
+    method_from_inter: HashMap<String, HashMap<String, String>>,
+    field_to_inter: HashMap<String, HashMap<String, String>>,
+    field_from_inter: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Maps {
+    tables: HashMap<Ns, NsMaps>,
+    // intermediary 下每个类的成员 (名称, 描述符)，描述符未知时为空
+    methods_by_class: HashMap<String, BTreeSet<(String, String)>>,
+    fields_by_class: HashMap<String, BTreeSet<(String, String)>>,
+}
+
+fn method_key(name: &str, desc: &str) -> String {
+    format!("{}{}", name, desc)
+}
+
+fn merge_members(
+    into: &mut HashMap<String, HashMap<String, String>>,
+    from: &HashMap<String, HashMap<String, String>>,
+) {
+    for (owner, members) in from {
+        into.entry(owner.clone())
+            .or_default()
+            .extend(members.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+impl Maps {
+    fn merge(&mut self, other: &Maps) {
+        for (ns, t) in &other.tables {
+            let own = self.tables.entry(*ns).or_default();
+            own.class_to_inter.extend(t.class_to_inter.clone());
+            own.class_from_inter.extend(t.class_from_inter.clone());
+            merge_members(&mut own.method_to_inter, &t.method_to_inter);
+            merge_members(&mut own.method_from_inter, &t.method_from_inter);
+            merge_members(&mut own.field_to_inter, &t.field_to_inter);
+            merge_members(&mut own.field_from_inter, &t.field_from_inter);
+        }
+        for (owner, set) in &other.methods_by_class {
+            self.methods_by_class
+                .entry(owner.clone())
+                .or_default()
+                .extend(set.iter().cloned());
+        }
+        for (owner, set) in &other.fields_by_class {
+            self.fields_by_class
+                .entry(owner.clone())
+                .or_default()
+                .extend(set.iter().cloned());
         }
     }
-    let class_maps = ClassMaps {
-        yarn_named_to_inter: class_named_to_inter.clone(),
-        yarn_inter_to_named: class_inter_to_named.clone(),
-        moj_inter_to_named: HashMap::new(),
-        moj_named_to_inter: HashMap::new(),
-    };
-    let method_maps = MemberMaps {
-        yarn_named_to_inter_by_class: method_named_to_inter_by_class,
-        yarn_inter_to_named_by_class: method_inter_to_named_by_class,
-        moj_inter_to_named_by_class: HashMap::new(),
-        moj_named_to_inter_by_class: HashMap::new(),
-    };
-    let field_maps = MemberMaps {
-        yarn_named_to_inter_by_class: field_named_to_inter_by_class,
-        yarn_inter_to_named_by_class: field_inter_to_named_by_class,
-        moj_inter_to_named_by_class: HashMap::new(),
-        moj_named_to_inter_by_class: HashMap::new(),
-    };
-    (class_maps, method_maps, field_maps)
+
+    fn add_class(&mut self, ns: Ns, inter: &str, named: &str) {
+        let t = self.tables.entry(ns).or_default();
+        t.class_to_inter
+            .insert(named.to_string(), inter.to_string());
+        t.class_from_inter
+            .insert(inter.to_string(), named.to_string());
+    }
+
+    fn add_method(
+        &mut self,
+        ns: Ns,
+        owner_inter: &str,
+        inter: &str,
+        desc_inter: &str,
+        named: &str,
+    ) {
+        let t = self.tables.entry(ns).or_default();
+        t.method_to_inter
+            .entry(owner_inter.to_string())
+            .or_default()
+            .insert(method_key(named, desc_inter), inter.to_string());
+        t.method_from_inter
+            .entry(owner_inter.to_string())
+            .or_default()
+            .insert(method_key(inter, desc_inter), named.to_string());
+        self.methods_by_class
+            .entry(owner_inter.to_string())
+            .or_default()
+            .insert((inter.to_string(), desc_inter.to_string()));
+    }
+
+    fn add_field(&mut self, ns: Ns, owner_inter: &str, inter: &str, desc_inter: &str, named: &str) {
+        let t = self.tables.entry(ns).or_default();
+        t.field_to_inter
+            .entry(owner_inter.to_string())
+            .or_default()
+            .insert(named.to_string(), inter.to_string());
+        t.field_from_inter
+            .entry(owner_inter.to_string())
+            .or_default()
+            .insert(inter.to_string(), named.to_string());
+        self.fields_by_class
+            .entry(owner_inter.to_string())
+            .or_default()
+            .insert((inter.to_string(), desc_inter.to_string()));
+    }
 }
 
-fn build_maps_from_moj(v: &Value, mut c: ClassMaps, mut m: MemberMaps, mut f: MemberMaps) -> Maps {
-    let mut class_inter_to_named = HashMap::new();
-    let mut class_named_to_inter = HashMap::new();
-    let mut method_inter_to_named_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    let mut method_named_to_inter_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    let mut field_inter_to_named_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
-    let mut field_named_to_inter_by_class: HashMap<String, HashMap<String, String>> =
-        HashMap::new();
+fn member_kind_of_inter(name: &str, desc: &str) -> Option<EntryKind> {
+    if desc.starts_with('(') || name.starts_with("method_") {
+        Some(EntryKind::Method)
+    } else if name.starts_with("field_") || name.starts_with("comp_") || !desc.is_empty() {
+        Some(EntryKind::Field)
+    } else {
+        None
+    }
+}
+
+fn load_linkie_namespace(maps: &mut Maps, ns: Ns, v: &Value) {
     if let Value::Object(map) = v {
         for (inter, obj) in map {
-            let mut named_opt = None;
             if let Value::Object(obj_map) = obj {
                 for (k, val) in obj_map {
                     if k == "mappedName" {
                         if let Some(named) = val.as_str() {
-                            named_opt = Some(named.to_string());
+                            maps.add_class(ns, inter, named);
                         }
                         continue;
                     }
-                    if let Some(named) = val.as_str() {
-                        method_inter_to_named_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(k.clone(), named.to_string());
-                        method_named_to_inter_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(named.to_string(), k.clone());
-                        field_inter_to_named_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(k.clone(), named.to_string());
-                        field_named_to_inter_by_class
-                            .entry(inter.clone())
-                            .or_default()
-                            .insert(named.to_string(), k.clone());
+                    let (named, desc) = match val {
+                        Value::String(named) => (named.clone(), String::new()),
+                        Value::Object(o) => match o.get("mappedName").and_then(|n| n.as_str()) {
+                            Some(named) => (
+                                named.to_string(),
+                                o.get("desc")
+                                    .or_else(|| o.get("descriptor"))
+                                    .and_then(|d| d.as_str())
+                                    .unwrap_or("")
+                                    .to_string(),
+                            ),
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    let (member, desc) = match k.find('(') {
+                        Some(i) => (k[..i].to_string(), k[i..].to_string()),
+                        None => (k.clone(), desc),
+                    };
+                    match member_kind_of_inter(&member, &desc) {
+                        Some(EntryKind::Method) => {
+                            maps.add_method(ns, inter, &member, &desc, &named)
+                        }
+                        Some(EntryKind::Field) => maps.add_field(ns, inter, &member, &desc, &named),
+                        _ => {
+                            // 无法从名称判断类型时两边都登记
+                            maps.add_method(ns, inter, &member, &desc, &named);
+                            maps.add_field(ns, inter, &member, &desc, &named);
+                        }
                     }
                 }
             }
-            if let Some(named) = named_opt {
-                class_inter_to_named.insert(inter.clone(), named.clone());
-                class_named_to_inter.insert(named.clone(), inter.clone());
-            }
         }
     }
-    c.moj_inter_to_named = class_inter_to_named.clone();
-    c.moj_named_to_inter = class_named_to_inter.clone();
-    m.moj_inter_to_named_by_class = method_inter_to_named_by_class;
-    m.moj_named_to_inter_by_class = method_named_to_inter_by_class;
-    f.moj_inter_to_named_by_class = field_inter_to_named_by_class;
-    f.moj_named_to_inter_by_class = field_named_to_inter_by_class;
-    Maps {
-        class_maps: c,
-        method_maps: m,
-        field_maps: f,
-    }
 }
 
-async fn prepare_maps(version: &str) -> anyhow::Result<Maps> {
-    let yarn = fetch_mappings("yarn", version).await?;
-    let moj = fetch_mappings("mojang", version).await?;
-    let (c, m, f) = build_maps_from_yarn(&yarn);
-    Ok(build_maps_from_moj(&moj, c, m, f))
+// Fabric 发布的 intermediary 文件，用来把混淆名挂到 intermediary 上
+async fn fetch_intermediary(version: &str) -> anyhow::Result<String> {
+    let cache_name = format!(
+        "mappings-intermediary-{}.bin",
+        crate::cache::safe_key_segment(version)
+    );
+    if let Ok(text) = crate::cache::read_bincode::<String>(&cache_name) {
+        return Ok(text);
+    }
+    if crate::util::is_offline() {
+        return Err(crate::cache::offline_miss(&format!(
+            "intermediary mappings for {}",
+            version
+        )));
+    }
+    let client = crate::util::http_client()?;
+    let url = format!(
+        "https://raw.githubusercontent.com/FabricMC/intermediary/master/mappings/{}.tiny",
+        version
+    );
+    let resp = crate::util::send_with_retry(client.get(url), 2).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Intermediary mappings for {} not available: {}",
+            version,
+            resp.status()
+        ));
+    }
+    let text = resp.text().await?;
+    crate::cache::write_bincode(&cache_name, &text)?;
+    Ok(text)
 }
 
-#[derive(Clone, Copy)]
-enum Ns {
-    Yarn,
-    Official,
+// MCPConfig 中的 joined.tsrg，1.16 为 TSRG，1.17 起为 TSRG2
+async fn fetch_mcp_config(version: &str) -> anyhow::Result<String> {
+    let cache_name = format!(
+        "mappings-mcpconfig-{}.bin",
+        crate::cache::safe_key_segment(version)
+    );
+    if let Ok(text) = crate::cache::read_bincode::<String>(&cache_name) {
+        return Ok(text);
+    }
+    if crate::util::is_offline() {
+        return Err(crate::cache::offline_miss(&format!(
+            "MCPConfig mappings for {}",
+            version
+        )));
+    }
+    let client = crate::util::http_client()?;
+    let base = "https://maven.minecraftforge.net/de/oceanlabs/mcp/mcp_config";
+    let meta = crate::util::send_with_retry(client.get(format!("{}/maven-metadata.xml", base)), 2)
+        .await?
+        .text()
+        .await?;
+    let prefix = format!("{}-", version);
+    let full = MAVEN_VERSION_RE
+        .captures_iter(&meta)
+        .map(|c| c[1].to_string())
+        .filter(|v| v == version || v.starts_with(&prefix))
+        .max()
+        .ok_or_else(|| anyhow::anyhow!("No MCPConfig release for Minecraft {}", version))?;
+    let url = format!("{}/{}/mcp_config-{}.zip", base, full, full);
+    let resp = crate::util::send_with_retry(client.get(url), 2).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "MCPConfig download failed: {}",
+            resp.status()
+        ));
+    }
+    let bytes = resp.bytes().await?;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("config/joined.tsrg")?, &mut text)?;
+    crate::util::log_event("info", &format!("mcp_config_fetched {} {}", version, full));
+    crate::cache::write_bincode(&cache_name, &text)?;
+    Ok(text)
 }
 
-fn ns_of(s: &str) -> Ns {
-    match s.to_lowercase().as_str() {
-        "yarn" => Ns::Yarn,
-        _ => Ns::Official,
+// Quilt 发布的 hashed 映射，jar 内为 official -> hashed 的 Tiny 文件
+async fn fetch_hashed(version: &str) -> anyhow::Result<String> {
+    let cache_name = format!(
+        "mappings-hashed-{}.bin",
+        crate::cache::safe_key_segment(version)
+    );
+    if let Ok(text) = crate::cache::read_bincode::<String>(&cache_name) {
+        return Ok(text);
+    }
+    if crate::util::is_offline() {
+        return Err(crate::cache::offline_miss(&format!(
+            "hashed mappings for {}",
+            version
+        )));
+    }
+    let client = crate::util::http_client()?;
+    let url = format!(
+        "https://maven.quiltmc.org/repository/release/org/quiltmc/hashed/{}/hashed-{}.jar",
+        version, version
+    );
+    let resp = crate::util::send_with_retry(client.get(url), 2).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Hashed mappings for {} not available: {}",
+            version,
+            resp.status()
+        ));
     }
+    let bytes = resp.bytes().await?;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    let entry = archive
+        .file_names()
+        .find(|n| n.ends_with(".tiny"))
+        .map(|n| n.to_string())
+        .ok_or_else(|| anyhow::anyhow!("No Tiny file in hashed mappings for {}", version))?;
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name(&entry)?, &mut text)?;
+    crate::cache::write_bincode(&cache_name, &text)?;
+    Ok(text)
 }
 
-struct Stats {
-    converted: usize,
-    failed: usize,
-    failures: Vec<String>,
+async fn load_intermediary(maps: &mut Maps, version: &str) -> anyhow::Result<()> {
+    let inter = fetch_intermediary(version).await?;
+    let inter = crate::mappings::parse(&inter, crate::mappings::MappingFormat::TinyV1)?;
+    load_tree(maps, &inter, Ns::Intermediary)
 }
 
-fn to_inter_class(maps: &Maps, ns: Ns, named: &str) -> String {
+async fn load_srg(maps: &mut Maps, version: &str) -> anyhow::Result<()> {
+    load_intermediary(maps, version).await?;
+    let tsrg = fetch_mcp_config(version).await?;
+    let tree = crate::mappings::parse(&tsrg, crate::mappings::detect_format(&tsrg)?)?;
+    load_tree(maps, &tree, Ns::Srg)
+}
+
+async fn load_hashed(maps: &mut Maps, version: &str) -> anyhow::Result<()> {
+    load_intermediary(maps, version).await?;
+    let hashed = fetch_hashed(version).await?;
+    let tree = crate::mappings::parse(&hashed, crate::mappings::detect_format(&hashed)?)?;
+    load_tree(maps, &tree, Ns::Hashed)
+}
+
+async fn load_official(maps: &mut Maps, version: &str) -> anyhow::Result<()> {
+    load_intermediary(maps, version).await?;
+    let client = crate::mojang::client_mappings(version).await?;
+    let client = crate::mappings::parse(&client, crate::mappings::MappingFormat::ProGuard)?;
+    load_tree(maps, &client, Ns::Official)
+}
+
+// 每个命名空间的解析结果按版本单独缓存，内存中保留到会话结束
+async fn namespace_set(ns: &str, version: &str) -> anyhow::Result<Arc<Maps>> {
+    let cache_name = format!(
+        "mappings-set-{}-{}.bin",
+        ns,
+        crate::cache::safe_key_segment(version)
+    );
+    if let Some(maps) = crate::cache::read_session_bincode::<Maps>(&cache_name) {
+        return Ok(maps);
+    }
+    let mut maps = Maps::default();
+    let mut persist = true;
     match ns {
-        Ns::Yarn => maps
-            .class_maps
-            .yarn_named_to_inter
-            .get(named)
-            .cloned()
-            .unwrap_or_else(|| named.to_string()),
-        Ns::Official => maps
-            .class_maps
-            .moj_named_to_inter
-            .get(named)
-            .cloned()
-            .unwrap_or_else(|| named.to_string()),
+        "yarn" => {
+            let yarn = fetch_mappings("yarn", version).await?;
+            load_linkie_namespace(&mut maps, Ns::Yarn, &yarn);
+        }
+        "mojang" => {
+            // 官方 client_mappings 为准，取不到时退回 Linkie 的 mojang 数据且不落盘
+            if let Err(e) = load_official(&mut maps, version).await {
+                crate::util::log_event(
+                    "warn",
+                    &format!("official_mappings_unavailable {} {}", version, e),
+                );
+                maps = Maps::default();
+                let moj = fetch_mappings("mojang", version).await?;
+                load_linkie_namespace(&mut maps, Ns::Official, &moj);
+                persist = false;
+            }
+        }
+        "srg" => load_srg(&mut maps, version).await?,
+        "quilt-mappings" => {
+            let quilt = fetch_mappings("quilt-mappings", version).await?;
+            load_linkie_namespace(&mut maps, Ns::Quilt, &quilt);
+        }
+        "hashed" => load_hashed(&mut maps, version).await?,
+        other => return Err(anyhow::anyhow!("Unknown mapping set: {}", other)),
+    }
+    let maps = Arc::new(maps);
+    if !persist {
+        crate::cache::session_put(&cache_name, maps.clone());
+    } else if let Err(e) = crate::cache::write_session_bincode(&cache_name, maps.clone()) {
+        crate::util::log_event(
+            "warn",
+            &format!("mapping_cache_write_failed {} {}", cache_name, e),
+        );
     }
+    Ok(maps)
 }
 
-fn from_inter_class(maps: &Maps, ns: Ns, inter: &str) -> String {
-    match ns {
-        Ns::Yarn => maps
-            .class_maps
-            .yarn_inter_to_named
-            .get(inter)
-            .cloned()
-            .unwrap_or_else(|| inter.to_string()),
-        Ns::Official => maps
-            .class_maps
-            .moj_inter_to_named
-            .get(inter)
-            .cloned()
-            .unwrap_or_else(|| inter.to_string()),
-    }
-}
-
-fn to_inter_member(
-    maps: &Maps,
-    ns: Ns,
-    owner_inter: &str,
-    member_named: &str,
-    is_method: bool,
-) -> String {
-    match (ns, is_method) {
-        (Ns::Yarn, true) => maps
-            .method_maps
-            .yarn_named_to_inter_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_named))
-            .cloned()
-            .unwrap_or_else(|| member_named.to_string()),
-        (Ns::Yarn, false) => maps
-            .field_maps
-            .yarn_named_to_inter_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_named))
-            .cloned()
-            .unwrap_or_else(|| member_named.to_string()),
-        (Ns::Official, true) => maps
-            .method_maps
-            .moj_named_to_inter_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_named))
-            .cloned()
-            .unwrap_or_else(|| member_named.to_string()),
-        (Ns::Official, false) => maps
-            .field_maps
-            .moj_named_to_inter_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_named))
-            .cloned()
-            .unwrap_or_else(|| member_named.to_string()),
+async fn load_maps(
+    mapping_files: Option<Vec<MappingFileInput>>,
+    mc_version: anyhow::Result<String>,
+    sets: &[&str],
+) -> anyhow::Result<Maps> {
+    match mapping_files.filter(|f| !f.is_empty()) {
+        Some(files) => load_local_maps(&files),
+        None => prepare_maps(&mc_version?, sets).await,
     }
 }
 
-fn from_inter_member(
-    maps: &Maps,
-    ns: Ns,
-    owner_inter: &str,
-    member_inter: &str,
-    is_method: bool,
-) -> String {
-    match (ns, is_method) {
-        (Ns::Yarn, true) => maps
-            .method_maps
-            .yarn_inter_to_named_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_inter))
-            .cloned()
-            .unwrap_or_else(|| member_inter.to_string()),
-        (Ns::Yarn, false) => maps
-            .field_maps
-            .yarn_inter_to_named_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_inter))
-            .cloned()
-            .unwrap_or_else(|| member_inter.to_string()),
-        (Ns::Official, true) => maps
-            .method_maps
-            .moj_inter_to_named_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_inter))
-            .cloned()
-            .unwrap_or_else(|| member_inter.to_string()),
-        (Ns::Official, false) => maps
-            .field_maps
-            .moj_inter_to_named_by_class
-            .get(owner_inter)
-            .and_then(|m| m.get(member_inter))
-            .cloned()
-            .unwrap_or_else(|| member_inter.to_string()),
+async fn prepare_maps(version: &str, sets: &[&str]) -> anyhow::Result<Maps> {
+    let mut maps = Maps::default();
+    for ns in sets {
+        let set = namespace_set(ns, version).await?;
+        maps.merge(&set);
     }
+    Ok(maps)
 }
 
-fn convert_desc_adv(
-    desc: &str,
-    from_named_to_inter: &HashMap<String, String>,
-    to_inter_to_named: &HashMap<String, String>,
-) -> String {
-    let mut out = String::new();
-    let mut i = 0;
-    let bytes = desc.as_bytes();
-    while i < bytes.len() {
-        let c = bytes[i] as char;
-        if c == 'L' {
-            let mut j = i + 1;
-            let mut class_buf = String::new();
-            while j < bytes.len() {
-                let cj = bytes[j] as char;
-                if cj == '<' || cj == ';' {
-                    break;
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingFileInput {
+    path: String,
+    format: Option<String>,
+    // Tiny 文件中 named 列对应的命名空间，默认 yarn
+    named_namespace: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConvertOptions {
+    mapping_files: Option<Vec<MappingFileInput>>,
+    // 决定 AT 使用 SRG 还是 Mojang 名
+    loader: Option<String>,
+    // 设置后把条目移植到该 Minecraft 版本
+    target_mc_version: Option<String>,
+    // 在输出文件旁写出逐行报告
+    write_report: bool,
+    // 相对路径以输入文件所在目录为基准
+    output_path: Option<String>,
+    // overwrite / merge / fail / suffix，默认 overwrite
+    on_conflict: Option<String>,
+    // 只返回内容，不写任何文件
+    dry_run: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ConflictPolicy {
+    Overwrite,
+    Merge,
+    Fail,
+    Suffix,
+}
+
+fn conflict_policy_of(s: Option<&str>) -> anyhow::Result<ConflictPolicy> {
+    match s.map(|s| s.to_lowercase()).as_deref() {
+        None | Some("overwrite") => Ok(ConflictPolicy::Overwrite),
+        Some("merge") => Ok(ConflictPolicy::Merge),
+        Some("fail") => Ok(ConflictPolicy::Fail),
+        Some("suffix") => Ok(ConflictPolicy::Suffix),
+        Some(other) => Err(anyhow::anyhow!("Unknown conflict policy: {}", other)),
+    }
+}
+
+fn column_ns(column: &str, named_ns: Ns) -> Option<Ns> {
+    match column.to_lowercase().as_str() {
+        "intermediary" => Some(Ns::Intermediary),
+        // Tiny 中的 official 指混淆名
+        "official" | "obf" | "notch" => Some(Ns::Obf),
+        "named" => Some(named_ns),
+        "yarn" => Some(Ns::Yarn),
+        "mojang" | "mojmap" => Some(Ns::Official),
+        "srg" => Some(Ns::Srg),
+        "quilt-mappings" | "quilt" => Some(Ns::Quilt),
+        "hashed" => Some(Ns::Hashed),
+        _ => None,
+    }
+}
+
+fn load_tree(
+    maps: &mut Maps,
+    tree: &crate::mappings::MappingTree,
+    named_ns: Ns,
+) -> anyhow::Result<()> {
+    let columns: Vec<Option<Ns>> = tree
+        .namespaces
+        .iter()
+        .map(|c| column_ns(c, named_ns))
+        .collect();
+    // 通过 intermediary 列或已加载的命名空间把条目挂到 intermediary 上
+    let link = columns
+        .iter()
+        .position(|c| *c == Some(Ns::Intermediary))
+        .or_else(|| {
+            columns
+                .iter()
+                .position(|c| matches!(c, Some(ns) if maps.tables.contains_key(ns)))
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Mapping namespaces [{}] cannot be linked to intermediary; load an intermediary Tiny file first",
+                tree.namespaces.join(", ")
+            )
+        })?;
+    let link_ns = columns[link].unwrap();
+    let desc_ns = columns[0];
+
+    let mut class_inter: HashMap<String, String> = HashMap::new();
+    let mut classes = Vec::new();
+    for class in &tree.classes {
+        let linked = match class.names.get(link).filter(|n| !n.is_empty()) {
+            Some(n) => n,
+            None => continue,
+        };
+        if let Some(inter) = class_to_inter_opt(maps, link_ns, linked) {
+            class_inter.insert(class.names[0].clone(), inter.clone());
+            classes.push((class, inter));
+        }
+    }
+    let desc_to_inter = |desc: &str| {
+        convert_desc_adv(desc, &|c| {
+            class_inter
+                .get(c)
+                .cloned()
+                .or_else(|| class_to_inter_opt(maps, desc_ns?, c))
+        })
+    };
+
+    let mut new_classes = Vec::new();
+    let mut new_methods = Vec::new();
+    let mut new_fields = Vec::new();
+    for (class, inter) in &classes {
+        for (col, ns) in columns.iter().enumerate() {
+            match (ns, class.names.get(col)) {
+                (Some(ns), Some(name)) if *ns != Ns::Intermediary && !name.is_empty() => {
+                    new_classes.push((*ns, inter.clone(), name.clone()))
                 }
-                class_buf.push(cj);
-                j += 1;
+                _ => {}
             }
-            let inter = from_named_to_inter.get(&class_buf).cloned();
-            let mapped_class = inter
-                .as_ref()
-                .and_then(|it| to_inter_to_named.get(it))
-                .cloned()
-                .unwrap_or_else(|| class_buf.clone());
-            out.push('L');
-            out.push_str(&mapped_class);
-            if j < bytes.len() && bytes[j] as char == '<' {
-                out.push('<');
-                j += 1;
-                let mut depth = 1;
-                let mut segment = String::new();
-                while j < bytes.len() && depth > 0 {
-                    let cj = bytes[j] as char;
-                    if cj == '<' {
-                        depth += 1;
-                        segment.push(cj);
-                    } else if cj == '>' {
-                        depth -= 1;
-                        segment.push(cj);
-                    } else {
-                        segment.push(cj);
+        }
+        for m in &class.methods {
+            let desc_inter = desc_to_inter(&m.desc);
+            let linked = &m.names[link];
+            let member_inter = if link_ns == Ns::Intermediary {
+                linked.clone()
+            } else {
+                match maps.tables.get(&link_ns).and_then(|t| {
+                    lookup_member(&t.method_to_inter, inter, &method_key(linked, &desc_inter))
+                        .or_else(|| lookup_member(&t.method_to_inter, inter, linked))
+                }) {
+                    Some(n) => n,
+                    None => continue,
+                }
+            };
+            for (col, ns) in columns.iter().enumerate() {
+                match (ns, m.names.get(col)) {
+                    (Some(ns), Some(name)) if *ns != Ns::Intermediary && !name.is_empty() => {
+                        new_methods.push((
+                            *ns,
+                            inter.clone(),
+                            member_inter.clone(),
+                            desc_inter.clone(),
+                            name.clone(),
+                        ))
                     }
-                    j += 1;
+                    _ => {}
                 }
-                let converted_segment =
-                    convert_desc_adv(&segment, from_named_to_inter, to_inter_to_named);
-                out.push_str(&converted_segment);
-                out.push('>');
-            }
-            while j < bytes.len() && bytes[j] as char != ';' {
-                j += 1;
             }
-            if j < bytes.len() && bytes[j] as char == ';' {
-                out.push(';');
-                j += 1;
+        }
+        for f in &class.fields {
+            let desc_inter = desc_to_inter(&f.desc);
+            let linked = &f.names[link];
+            let member_inter = if link_ns == Ns::Intermediary {
+                linked.clone()
+            } else {
+                match maps
+                    .tables
+                    .get(&link_ns)
+                    .and_then(|t| lookup_member(&t.field_to_inter, inter, linked))
+                {
+                    Some(n) => n,
+                    None => continue,
+                }
+            };
+            let desc_inter = if desc_inter.is_empty() {
+                field_desc_inter(maps, inter, &member_inter)
+            } else {
+                desc_inter
+            };
+            for (col, ns) in columns.iter().enumerate() {
+                match (ns, f.names.get(col)) {
+                    (Some(ns), Some(name)) if *ns != Ns::Intermediary && !name.is_empty() => {
+                        new_fields.push((
+                            *ns,
+                            inter.clone(),
+                            member_inter.clone(),
+                            desc_inter.clone(),
+                            name.clone(),
+                        ))
+                    }
+                    _ => {}
+                }
             }
-            i = j;
-            continue;
-        } else {
-            out.push(c);
-            i += 1;
         }
     }
-    out
-}
 
-fn parse_aw_line(line: &str) -> Option<(String, String, Vec<String>)> {
-    let trimmed = line.trim();
-    if trimmed.is_empty() || trimmed.starts_with('#') {
-        return None;
+    for (ns, inter, name) in new_classes {
+        maps.add_class(ns, &inter, &name);
     }
-    if trimmed.starts_with("accessWidener") {
-        return None;
+    for (ns, owner, inter, desc, name) in new_methods {
+        maps.add_method(ns, &owner, &inter, &desc, &name);
     }
-    let parts: Vec<String> = trimmed.split_whitespace().map(|s| s.to_string()).collect();
-    if parts.len() < 3 {
-        return None;
+    for (ns, owner, inter, desc, name) in new_fields {
+        maps.add_field(ns, &owner, &inter, &desc, &name);
     }
-    Some((parts[0].clone(), parts[1].clone(), parts[2..].to_vec()))
+    Ok(())
 }
 
-fn build_at_line_class(owner_official: &str) -> String {
-    format!("public {}", owner_official.replace('/', "."))
+fn load_local_maps(files: &[MappingFileInput]) -> anyhow::Result<Maps> {
+    let mut trees = Vec::new();
+    for f in files {
+        let tree = crate::mappings::load_file(Path::new(&f.path), f.format.as_deref())?;
+        let named_ns = f
+            .named_namespace
+            .as_deref()
+            .map(ns_of)
+            .transpose()?
+            .unwrap_or(Ns::Yarn);
+        trees.push((f, tree, named_ns));
+    }
+    // 含 intermediary 的文件先加载，其余文件才能挂靠；只有 Mojang 名的 Parchment 放在最后
+    trees.sort_by_key(|(_, tree, _)| {
+        if tree.namespaces.iter().any(|n| n == "intermediary") {
+            0
+        } else if is_mojang_only(tree) {
+            2
+        } else {
+            1
+        }
+    });
+    let mut maps = Maps::default();
+    for (f, tree, named_ns) in &trees {
+        if is_mojang_only(tree) {
+            if !maps.tables.contains_key(&Ns::Official) {
+                return Err(anyhow::anyhow!(
+                    "{} only contains Mojang names; load Mojang client mappings (ProGuard) together with it",
+                    f.path
+                ));
+            }
+            let unmatched = unmatched_entries(&maps, tree, Ns::Official);
+            if !unmatched.is_empty() {
+                crate::util::log_event(
+                    "warn",
+                    &format!(
+                        "mapping_file_unmatched {} count={} first={}",
+                        f.path,
+                        unmatched.len(),
+                        unmatched[0]
+                    ),
+                );
+            }
+        }
+        load_tree(&mut maps, tree, *named_ns)?;
+        crate::util::log_event(
+            "info",
+            &format!(
+                "mapping_file_loaded {} classes={}",
+                f.path,
+                tree.classes.len()
+            ),
+        );
+    }
+    Ok(maps)
 }
 
-fn build_at_line_field(owner_official: &str, member_inter: &str) -> String {
-    format!(
-        "public {} {}",
-        owner_official.replace('/', "."),
-        member_inter
-    )
+fn is_mojang_only(tree: &crate::mappings::MappingTree) -> bool {
+    tree.namespaces.len() == 1 && column_ns(&tree.namespaces[0], Ns::Yarn) == Some(Ns::Official)
 }
 
-fn build_at_line_method(owner_official: &str, member_inter: &str, desc_official: &str) -> String {
-    format!(
-        "public {} {}{}",
-        owner_official.replace('/', "."),
-        member_inter,
-        desc_official
-    )
+// 在已加载的命名空间中找不到的类与成员，用于校验 Parchment 等单列文件
+fn unmatched_entries(maps: &Maps, tree: &crate::mappings::MappingTree, ns: Ns) -> Vec<String> {
+    let mut out = Vec::new();
+    let Some(t) = maps.tables.get(&ns) else {
+        return out;
+    };
+    for class in &tree.classes {
+        let name = &class.names[0];
+        let Some(inter) = t.class_to_inter.get(name) else {
+            out.push(name.clone());
+            continue;
+        };
+        for m in &class.methods {
+            // 构造器不参与 intermediary 映射
+            if m.names[0].starts_with('<') {
+                continue;
+            }
+            let desc_inter = translate_desc(maps, ns, Ns::Intermediary, &m.desc);
+            let key = method_key(&m.names[0], &desc_inter);
+            if lookup_member(&t.method_to_inter, inter, &key).is_none() {
+                out.push(format!("{}.{}{}", name, m.names[0], m.desc));
+            }
+        }
+        for f in &class.fields {
+            if lookup_member(&t.field_to_inter, inter, &f.names[0]).is_none() {
+                out.push(format!("{}.{}", name, f.names[0]));
+            }
+        }
+    }
+    out
 }
 
-fn build_aw_header(_ns: &str) -> String {
-    "accessWidener v2 named".to_string()
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+enum Ns {
+    Yarn,
+    Official,
+    Intermediary,
+    Obf,
+    Srg,
+    Quilt,
+    Hashed,
 }
 
-fn is_disclaimer_line(s: &str) -> bool {
-    let d = disclaimer_lines();
-    let trimmed = s.trim();
-    d.iter().any(|line| line.trim() == trimmed)
+fn ns_of(s: &str) -> anyhow::Result<Ns> {
+    match s.trim().to_lowercase().as_str() {
+        "yarn" => Ok(Ns::Yarn),
+        "official" | "mojang" | "mojmap" => Ok(Ns::Official),
+        "intermediary" => Ok(Ns::Intermediary),
+        "obf" | "notch" => Ok(Ns::Obf),
+        "srg" => Ok(Ns::Srg),
+        // MCP 名（func_xxx 之后的可读名）与 SRG 名不同，不能当作 srg 处理
+        "mcp" => Err(anyhow::anyhow!(
+            "MCP names are not supported; use srg for func_/m_ style names"
+        )),
+        "quilt-mappings" | "quilt" | "qm" => Ok(Ns::Quilt),
+        "hashed" | "hashed-mojmap" => Ok(Ns::Hashed),
+        other => Err(anyhow::anyhow!("Unknown mapping namespace: {}", other)),
+    }
 }
 
-fn is_aw_header_line(s: &str) -> bool {
-    let t = s.trim();
-    t.starts_with("accessWidener v")
+fn ns_opt(s: Option<&str>) -> Result<Option<Ns>, String> {
+    s.map(ns_of).transpose().map_err(|e| e.to_string())
 }
 
-fn sanitize_aw_input_lines(lines: &[String]) -> Vec<String> {
-    let mut out = Vec::with_capacity(lines.len());
-    for l in lines {
-        if is_disclaimer_line(l) || is_aw_header_line(l) {
-            continue;
+// 默认加载 yarn 与 mojang，其余命名空间按需追加
+fn mapping_sets(namespaces: &[Ns]) -> Vec<&'static str> {
+    let mut sets = vec!["yarn", "mojang"];
+    for ns in namespaces {
+        let extra = match ns {
+            Ns::Srg => "srg",
+            Ns::Quilt => "quilt-mappings",
+            Ns::Hashed => "hashed",
+            _ => continue,
+        };
+        if !sets.contains(&extra) {
+            sets.push(extra);
         }
-        out.push(l.clone());
     }
-    out
+    sets
 }
 
-fn aw_to_at_lines(lines: &[String], maps: &Maps, aw_ns: &str) -> (Vec<String>, Stats) {
-    let mut out: Vec<String> = Vec::new();
-    let mut stats = Stats {
-        converted: 0,
-        failed: 0,
-        failures: Vec::new(),
+fn ns_name(ns: Ns) -> &'static str {
+    match ns {
+        Ns::Yarn => "yarn",
+        Ns::Official => "official",
+        Ns::Intermediary => "intermediary",
+        Ns::Obf => "obf",
+        Ns::Srg => "srg",
+        Ns::Quilt => "quilt-mappings",
+        Ns::Hashed => "hashed",
+    }
+}
+
+// Forge 1.16 至 1.20.1 运行时使用 SRG 成员名，NeoForge 使用 Mojang 名
+fn default_at_ns(loader: Option<&str>, mc_version: &str) -> Ns {
+    let is_forge = loader
+        .map(|l| l.eq_ignore_ascii_case("forge"))
+        .unwrap_or(false);
+    let mut parts = mc_version
+        .split('.')
+        .map(|p| p.split('-').next().unwrap_or("").parse::<u32>().ok());
+    let (major, minor, patch) = (
+        parts.next().flatten(),
+        parts.next().flatten(),
+        parts.next().flatten().unwrap_or(0),
+    );
+    let srg_era = match (major, minor) {
+        (Some(1), Some(m)) => (16..=19).contains(&m) || (m == 20 && patch <= 1),
+        _ => false,
     };
-    out.extend(disclaimer_lines());
-    for line in lines {
-        let raw = line.trim();
-        if raw.is_empty() || raw.starts_with('#') {
-            out.push(line.clone());
-            continue;
-        }
-        if raw.starts_with("accessWidener") {
-            continue;
-        }
-        let parsed = parse_aw_line(raw);
-        if parsed.is_none() {
+    if is_forge && srg_era {
+        Ns::Srg
+    } else {
+        Ns::Official
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    converted: usize,
+    failed: usize,
+    failures: Vec<String>,
+    unsupported: Vec<String>,
+    // 跨版本转换时目标版本中已不存在的条目
+    missing: Vec<String>,
+    // 已输出但无法完全保留原语义的条目
+    approximated: Vec<String>,
+    report: Vec<LineReport>,
+    pending: Option<LineMark>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LineReport {
+    line: usize,
+    original: String,
+    output: Vec<String>,
+    status: &'static str,
+    reason: Option<String>,
+}
+
+// 记录某一输入行开始处理时各列表的长度，结束时按增量判断结果
+struct LineMark {
+    line: usize,
+    original: String,
+    out_len: usize,
+    failures_len: usize,
+    unsupported_len: usize,
+    missing_len: usize,
+    approximated_len: usize,
+}
+
+impl Stats {
+    fn open_line(&mut self, out: &[String], line: usize, original: &str) {
+        self.close_line(out);
+        self.pending = Some(LineMark {
+            line,
+            original: original.to_string(),
+            out_len: out.len(),
+            failures_len: self.failures.len(),
+            unsupported_len: self.unsupported.len(),
+            missing_len: self.missing.len(),
+            approximated_len: self.approximated.len(),
+        });
+    }
+
+    fn close_line(&mut self, out: &[String]) {
+        let mark = match self.pending.take() {
+            Some(m) => m,
+            None => return,
+        };
+        let output = out[mark.out_len..].to_vec();
+        let raw = mark.original.trim();
+        let missing = &self.missing[mark.missing_len..];
+        let unsupported = &self.unsupported[mark.unsupported_len..];
+        let failures = &self.failures[mark.failures_len..];
+        let approximated = &self.approximated[mark.approximated_len..];
+        let (status, reason) = if !missing.is_empty() {
+            ("missing", Some(missing.join("; ")))
+        } else if !unsupported.is_empty() {
+            let unparseable = unsupported
+                .iter()
+                .all(|u| u.starts_with("unparseable") || u.starts_with("unknown"));
+            let status = if unparseable {
+                "skipped-unparseable"
+            } else {
+                "skipped-unsupported"
+            };
+            (status, Some(unsupported.join("; ")))
+        } else if !failures.is_empty() {
+            ("unmapped", Some(failures.join("; ")))
+        } else if !approximated.is_empty() {
+            ("approximated", Some(approximated.join("; ")))
+        } else if is_disclaimer_line(raw) || is_aw_header_line(raw) {
+            (
+                "passthrough",
+                Some("replaced by generated header".to_string()),
+            )
+        } else if raw.is_empty() || raw.starts_with('#') {
+            ("passthrough", None)
+        } else if output.is_empty() {
+            (
+                "skipped-unparseable",
+                Some("incomplete or unrecognised entry".to_string()),
+            )
+        } else {
+            ("converted", None)
+        };
+        self.report.push(LineReport {
+            line: mark.line,
+            original: mark.original,
+            output,
+            status,
+            reason,
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum EntryKind {
+    Class,
+    Method,
+    Field,
+}
+
+fn entry_kind_of(s: &str) -> Option<EntryKind> {
+    match s {
+        "class" => Some(EntryKind::Class),
+        "method" => Some(EntryKind::Method),
+        "field" => Some(EntryKind::Field),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum AwAccess {
+    Accessible,
+    Extendable,
+    Mutable,
+}
+
+fn parse_aw_access(s: &str) -> Option<(AwAccess, bool)> {
+    let (transitive, base) = match s.strip_prefix("transitive-") {
+        Some(base) => (true, base),
+        None => (false, s),
+    };
+    let access = match base {
+        "accessible" => AwAccess::Accessible,
+        "extendable" => AwAccess::Extendable,
+        "mutable" => AwAccess::Mutable,
+        _ => return None,
+    };
+    Some((access, transitive))
+}
+
+fn aw_access_str(access: AwAccess, transitive: bool) -> String {
+    let base = match access {
+        AwAccess::Accessible => "accessible",
+        AwAccess::Extendable => "extendable",
+        AwAccess::Mutable => "mutable",
+    };
+    if transitive {
+        format!("transitive-{}", base)
+    } else {
+        base.to_string()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum AtVisibility {
+    Private,
+    Default,
+    Protected,
+    Public,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum AtFinal {
+    Keep,
+    Add,
+    Remove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct AtModifier {
+    visibility: AtVisibility,
+    final_mod: AtFinal,
+}
+
+fn parse_at_modifier(s: &str) -> Option<AtModifier> {
+    let (base, final_mod) = if let Some(b) = s.strip_suffix("-f") {
+        (b, AtFinal::Remove)
+    } else if let Some(b) = s.strip_suffix("+f") {
+        (b, AtFinal::Add)
+    } else {
+        (s, AtFinal::Keep)
+    };
+    let visibility = match base {
+        "public" => AtVisibility::Public,
+        "protected" => AtVisibility::Protected,
+        "default" => AtVisibility::Default,
+        "private" => AtVisibility::Private,
+        _ => return None,
+    };
+    Some(AtModifier {
+        visibility,
+        final_mod,
+    })
+}
+
+fn at_modifier_str(m: AtModifier) -> String {
+    let base = match m.visibility {
+        AtVisibility::Public => "public",
+        AtVisibility::Protected => "protected",
+        AtVisibility::Default => "default",
+        AtVisibility::Private => "private",
+    };
+    match m.final_mod {
+        AtFinal::Keep => base.to_string(),
+        AtFinal::Add => format!("{}+f", base),
+        AtFinal::Remove => format!("{}-f", base),
+    }
+}
+
+fn aw_to_at_modifier(access: AwAccess, kind: EntryKind) -> Option<AtModifier> {
+    let (visibility, final_mod) = match (access, kind) {
+        (AwAccess::Accessible, _) => (AtVisibility::Public, AtFinal::Keep),
+        (AwAccess::Extendable, EntryKind::Class) => (AtVisibility::Public, AtFinal::Remove),
+        (AwAccess::Extendable, EntryKind::Method) => (AtVisibility::Protected, AtFinal::Remove),
+        (AwAccess::Mutable, EntryKind::Field) => (AtVisibility::Public, AtFinal::Remove),
+        (AwAccess::Extendable, EntryKind::Field) | (AwAccess::Mutable, _) => return None,
+    };
+    Some(AtModifier {
+        visibility,
+        final_mod,
+    })
+}
+
+// 返回对应的 AW 访问级别，以及无法完全保留原语义时的说明
+fn at_to_aw_accesses(
+    m: AtModifier,
+    kind: EntryKind,
+) -> Result<(Vec<AwAccess>, Option<String>), String> {
+    if m.visibility < AtVisibility::Protected {
+        return Err(format!(
+            "{} has no AW equivalent, AW can only widen to public",
+            at_modifier_str(m)
+        ));
+    }
+    if m.final_mod == AtFinal::Add {
+        return Err(format!(
+            "{} has no AW equivalent, AW cannot add final",
+            at_modifier_str(m)
+        ));
+    }
+    let remove_final = m.final_mod == AtFinal::Remove;
+    let out = match (kind, remove_final) {
+        (EntryKind::Class, false) => vec![AwAccess::Accessible],
+        (EntryKind::Class, true) => vec![AwAccess::Extendable],
+        (EntryKind::Method, false) => vec![AwAccess::Accessible],
+        (EntryKind::Method, true) if m.visibility == AtVisibility::Public => {
+            vec![AwAccess::Accessible, AwAccess::Extendable]
+        }
+        (EntryKind::Method, true) => vec![AwAccess::Extendable],
+        (EntryKind::Field, false) => vec![AwAccess::Accessible],
+        (EntryKind::Field, true) => vec![AwAccess::Accessible, AwAccess::Mutable],
+    };
+    // 只有 extendable method 保留 protected，其余 AW 访问级别都会变为 public
+    let widened =
+        m.visibility == AtVisibility::Protected && !(kind == EntryKind::Method && remove_final);
+    let note = widened.then(|| {
+        format!(
+            "{} widened to public, AW has no protected access",
+            at_modifier_str(m)
+        )
+    });
+    Ok((out, note))
+}
+
+fn class_to_inter_opt(maps: &Maps, ns: Ns, named: &str) -> Option<String> {
+    if ns == Ns::Intermediary {
+        return Some(named.to_string());
+    }
+    maps.tables.get(&ns)?.class_to_inter.get(named).cloned()
+}
+
+fn class_from_inter_opt(maps: &Maps, ns: Ns, inter: &str) -> Option<String> {
+    if ns == Ns::Intermediary {
+        return Some(inter.to_string());
+    }
+    maps.tables.get(&ns)?.class_from_inter.get(inter).cloned()
+}
+
+fn to_inter_class(maps: &Maps, ns: Ns, named: &str) -> String {
+    class_to_inter_opt(maps, ns, named).unwrap_or_else(|| named.to_string())
+}
+
+fn from_inter_class(maps: &Maps, ns: Ns, inter: &str) -> String {
+    class_from_inter_opt(maps, ns, inter).unwrap_or_else(|| inter.to_string())
+}
+
+fn lookup_member(
+    by_class: &HashMap<String, HashMap<String, String>>,
+    owner_inter: &str,
+    key: &str,
+) -> Option<String> {
+    by_class.get(owner_inter).and_then(|m| m.get(key)).cloned()
+}
+
+fn to_inter_method(maps: &Maps, ns: Ns, owner_inter: &str, name: &str, desc: &str) -> String {
+    if ns == Ns::Intermediary {
+        return name.to_string();
+    }
+    let desc_inter = translate_desc(maps, ns, Ns::Intermediary, desc);
+    maps.tables
+        .get(&ns)
+        .and_then(|t| {
+            lookup_member(
+                &t.method_to_inter,
+                owner_inter,
+                &method_key(name, &desc_inter),
+            )
+            .or_else(|| lookup_member(&t.method_to_inter, owner_inter, name))
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+fn from_inter_method(
+    maps: &Maps,
+    ns: Ns,
+    owner_inter: &str,
+    member_inter: &str,
+    desc_inter: &str,
+) -> String {
+    if ns == Ns::Intermediary {
+        return member_inter.to_string();
+    }
+    maps.tables
+        .get(&ns)
+        .and_then(|t| {
+            lookup_member(
+                &t.method_from_inter,
+                owner_inter,
+                &method_key(member_inter, desc_inter),
+            )
+            .or_else(|| lookup_member(&t.method_from_inter, owner_inter, member_inter))
+        })
+        .unwrap_or_else(|| member_inter.to_string())
+}
+
+fn to_inter_field(maps: &Maps, ns: Ns, owner_inter: &str, name: &str) -> String {
+    maps.tables
+        .get(&ns)
+        .and_then(|t| lookup_member(&t.field_to_inter, owner_inter, name))
+        .unwrap_or_else(|| name.to_string())
+}
+
+fn from_inter_field(maps: &Maps, ns: Ns, owner_inter: &str, member_inter: &str) -> String {
+    maps.tables
+        .get(&ns)
+        .and_then(|t| lookup_member(&t.field_from_inter, owner_inter, member_inter))
+        .unwrap_or_else(|| member_inter.to_string())
+}
+
+fn field_desc_inter(maps: &Maps, owner_inter: &str, member_inter: &str) -> String {
+    maps.fields_by_class
+        .get(owner_inter)
+        .and_then(|set| {
+            set.iter()
+                .find(|(name, desc)| name == member_inter && !desc.is_empty())
+        })
+        .map(|(_, desc)| desc.clone())
+        .unwrap_or_default()
+}
+
+fn translate_desc(maps: &Maps, from: Ns, to: Ns, desc: &str) -> String {
+    if from == to {
+        return desc.to_string();
+    }
+    convert_desc_adv(desc, &|class| {
+        let inter = class_to_inter_opt(maps, from, class)?;
+        class_from_inter_opt(maps, to, &inter)
+    })
+}
+
+fn convert_desc_adv(desc: &str, map_class: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    let bytes = desc.as_bytes();
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c == 'L' {
+            let mut j = i + 1;
+            let mut class_buf = String::new();
+            while j < bytes.len() {
+                let cj = bytes[j] as char;
+                if cj == '<' || cj == ';' {
+                    break;
+                }
+                class_buf.push(cj);
+                j += 1;
+            }
+            let mapped_class = map_class(&class_buf).unwrap_or_else(|| class_buf.clone());
+            out.push('L');
+            out.push_str(&mapped_class);
+            if j < bytes.len() && bytes[j] as char == '<' {
+                out.push('<');
+                j += 1;
+                let mut depth = 1;
+                let mut segment = String::new();
+                while j < bytes.len() && depth > 0 {
+                    let cj = bytes[j] as char;
+                    if cj == '<' {
+                        depth += 1;
+                        segment.push(cj);
+                    } else if cj == '>' {
+                        depth -= 1;
+                        segment.push(cj);
+                    } else {
+                        segment.push(cj);
+                    }
+                    j += 1;
+                }
+                let converted_segment = convert_desc_adv(&segment, map_class);
+                out.push_str(&converted_segment);
+                out.push('>');
+            }
+            while j < bytes.len() && bytes[j] as char != ';' {
+                j += 1;
+            }
+            if j < bytes.len() && bytes[j] as char == ';' {
+                out.push(';');
+                j += 1;
+            }
+            i = j;
+            continue;
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+    out
+}
+
+fn parse_aw_line(line: &str) -> Option<(String, String, Vec<String>)> {
+    let trimmed = line.split('#').next().unwrap_or("").trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    if trimmed.starts_with("accessWidener") {
+        return None;
+    }
+    let parts: Vec<String> = trimmed.split_whitespace().map(|s| s.to_string()).collect();
+    if parts.len() < 3 {
+        return None;
+    }
+    Some((parts[0].clone(), parts[1].clone(), parts[2..].to_vec()))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AtTarget {
+    Class,
+    Field(String),
+    Method(String, String),
+    AllFields,
+    AllMethods,
+}
+
+#[derive(Clone, Debug)]
+struct AtEntry {
+    modifier: AtModifier,
+    owner: String,
+    target: AtTarget,
+    comment: Option<String>,
+}
+
+fn parse_at_line(line: &str) -> Option<AtEntry> {
+    let (body, comment) = match line.find('#') {
+        Some(i) => (
+            &line[..i],
+            Some(line[i + 1..].trim().to_string()).filter(|c| !c.is_empty()),
+        ),
+        None => (line, None),
+    };
+    let mut parts = body.split_whitespace();
+    let modifier = parse_at_modifier(parts.next()?)?;
+    // 内部类保留 `$`，只把包分隔符换成 `/`
+    let owner = parts.next()?.replace('.', "/");
+    let target = match parts.next() {
+        None => AtTarget::Class,
+        Some("*") => AtTarget::AllFields,
+        Some("*()") => AtTarget::AllMethods,
+        Some(member) => match member.find('(') {
+            Some(i) => AtTarget::Method(member[..i].to_string(), member[i..].to_string()),
+            None => AtTarget::Field(member.to_string()),
+        },
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(AtEntry {
+        modifier,
+        owner,
+        target,
+        comment,
+    })
+}
+
+fn members_of(maps: &Maps, owner_inter: &str, is_method: bool) -> Vec<(String, String)> {
+    let by_class = if is_method {
+        &maps.methods_by_class
+    } else {
+        &maps.fields_by_class
+    };
+    let set = match by_class.get(owner_inter) {
+        Some(set) => set,
+        None => return Vec::new(),
+    };
+    // 同名成员既有带描述符又有不带描述符的记录时，只保留带描述符的
+    set.iter()
+        .filter(|(name, desc)| {
+            !desc.is_empty() || !set.iter().any(|(n, d)| n == name && !d.is_empty())
+        })
+        .cloned()
+        .collect()
+}
+
+fn build_at_line_class(modifier: AtModifier, owner: &str) -> String {
+    format!("{} {}", at_modifier_str(modifier), owner.replace('/', "."))
+}
+
+fn build_at_line_field(modifier: AtModifier, owner: &str, member: &str) -> String {
+    format!(
+        "{} {} {}",
+        at_modifier_str(modifier),
+        owner.replace('/', "."),
+        member
+    )
+}
+
+fn build_at_line_method(modifier: AtModifier, owner: &str, member: &str, desc: &str) -> String {
+    format!(
+        "{} {} {}{}",
+        at_modifier_str(modifier),
+        owner.replace('/', "."),
+        member,
+        desc
+    )
+}
+
+fn build_aw_header() -> String {
+    "accessWidener v2 named".to_string()
+}
+
+fn is_disclaimer_line(s: &str) -> bool {
+    let d = disclaimer_lines();
+    let trimmed = s.trim();
+    d.iter().any(|line| line.trim() == trimmed)
+}
+
+fn is_aw_header_line(s: &str) -> bool {
+    let t = s.trim();
+    t.starts_with("accessWidener v")
+}
+
+fn aw_to_at_lines(lines: &[String], maps: &Maps, ns: Ns, at_ns: Ns) -> (Vec<String>, Stats) {
+    let mut out: Vec<String> = Vec::new();
+    let mut stats = Stats::default();
+    out.extend(disclaimer_lines());
+    for (i, line) in lines.iter().enumerate() {
+        stats.open_line(&out, i + 1, line);
+        let raw = line.trim();
+        if is_disclaimer_line(raw) || is_aw_header_line(raw) {
+            continue;
+        }
+        if raw.is_empty() || raw.starts_with('#') {
+            out.push(line.clone());
+            continue;
+        }
+        if raw.starts_with("accessWidener") {
+            continue;
+        }
+        let parsed = parse_aw_line(raw);
+        if parsed.is_none() {
             continue;
         }
-        let (_access, typ, rest) = parsed.unwrap();
-        let ns = ns_of(aw_ns);
+        let (access_s, typ, rest) = parsed.unwrap();
+        let modifier = match (parse_aw_access(&access_s), entry_kind_of(&typ)) {
+            (Some((access, transitive)), Some(kind)) => match aw_to_at_modifier(access, kind) {
+                Some(m) => {
+                    // AT 没有传递性，也无法只去掉 final 而不改可见性
+                    if transitive {
+                        stats.approximated.push(format!(
+                            "{} {} emitted as non-transitive, AT has no transitive access: {}",
+                            access_s, typ, raw
+                        ));
+                    }
+                    if access == AwAccess::Mutable {
+                        stats.approximated.push(format!(
+                            "{} {} also widens visibility to public: {}",
+                            access_s, typ, raw
+                        ));
+                    }
+                    m
+                }
+                None => {
+                    stats.unsupported.push(format!(
+                        "{} {} has no AT equivalent: {}",
+                        access_s, typ, raw
+                    ));
+                    continue;
+                }
+            },
+            _ => {
+                stats.unsupported.push(format!("unknown AW entry: {}", raw));
+                continue;
+            }
+        };
         if typ == "class" {
             let owner_named = rest.get(0).cloned().unwrap_or_default();
             let owner_inter = to_inter_class(maps, ns, &owner_named);
-            let owner_official = from_inter_class(maps, Ns::Official, &owner_inter);
-            out.push(build_at_line_class(&owner_official));
+            let owner_at = from_inter_class(maps, at_ns, &owner_inter);
+            out.push(build_at_line_class(modifier, &owner_at));
             if owner_inter == owner_named {
                 stats.failed += 1;
                 stats
@@ -498,28 +1435,24 @@ fn aw_to_at_lines(lines: &[String], maps: &Maps, aw_ns: &str) -> (Vec<String>, S
                 "".to_string()
             };
             let owner_inter = to_inter_class(maps, ns, &owner_named);
-            let owner_official = from_inter_class(maps, Ns::Official, &owner_inter);
-            if typ == "method" {
-                let member_inter = to_inter_member(maps, ns, &owner_inter, &name, true);
-                let desc_official = match ns {
-                    Ns::Yarn => convert_desc_adv(
-                        &desc,
-                        &maps.class_maps.yarn_named_to_inter,
-                        &maps.class_maps.moj_inter_to_named,
-                    ),
-                    Ns::Official => desc.clone(),
-                };
+            let owner_at = from_inter_class(maps, at_ns, &owner_inter);
+            let member_inter = if typ == "method" {
+                let member_inter = to_inter_method(maps, ns, &owner_inter, &name, &desc);
+                let desc_inter = translate_desc(maps, ns, Ns::Intermediary, &desc);
+                let member_at =
+                    from_inter_method(maps, at_ns, &owner_inter, &member_inter, &desc_inter);
+                let desc_at = translate_desc(maps, ns, at_ns, &desc);
                 out.push(build_at_line_method(
-                    &owner_official,
-                    &member_inter,
-                    &desc_official,
+                    modifier, &owner_at, &member_at, &desc_at,
                 ));
+                member_inter
             } else {
-                let member_inter = to_inter_member(maps, ns, &owner_inter, &name, false);
-                out.push(build_at_line_field(&owner_official, &member_inter));
-            }
-            let fallback = owner_inter == owner_named
-                || to_inter_member(maps, ns, &owner_inter, &name, typ == "method") == name;
+                let member_inter = to_inter_field(maps, ns, &owner_inter, &name);
+                let member_at = from_inter_field(maps, at_ns, &owner_inter, &member_inter);
+                out.push(build_at_line_field(modifier, &owner_at, &member_at));
+                member_inter
+            };
+            let fallback = owner_inter == owner_named || member_inter == name;
             if fallback {
                 stats.failed += 1;
                 stats
@@ -531,120 +1464,246 @@ fn aw_to_at_lines(lines: &[String], maps: &Maps, aw_ns: &str) -> (Vec<String>, S
             continue;
         }
     }
+    stats.close_line(&out);
     (out, stats)
 }
 
-fn at_to_aw_lines(lines: &[String], maps: &Maps, target_aw_ns: &str) -> (Vec<String>, Stats) {
+fn at_to_aw_lines(lines: &[String], maps: &Maps, ns: Ns, at_ns: Ns) -> (Vec<String>, Stats) {
     let mut out: Vec<String> = Vec::new();
-    let mut stats = Stats {
-        converted: 0,
-        failed: 0,
-        failures: Vec::new(),
-    };
+    let mut stats = Stats::default();
     out.extend(disclaimer_lines());
-    out.push(build_aw_header(target_aw_ns));
-    for line in lines {
+    out.push(build_aw_header());
+    for (i, line) in lines.iter().enumerate() {
+        stats.open_line(&out, i + 1, line);
         let raw = line.trim();
-        if raw.is_empty() || raw.starts_with('#') {
-            out.push(line.clone());
+        if is_disclaimer_line(raw) || is_aw_header_line(raw) {
             continue;
         }
-        if !raw.starts_with("public")
-            && !raw.starts_with("protected")
-            && !raw.starts_with("private")
-        {
+        if raw.is_empty() || raw.starts_with('#') {
+            out.push(line.clone());
             continue;
         }
-        let mut parts = raw.split_whitespace();
-        let _acc = parts.next();
-        let owner_dotted = parts.next().unwrap_or("");
-        let after_owner = parts.next().unwrap_or("");
-        let owner_official = owner_dotted.replace('.', "/");
-        let owner_inter = to_inter_class(maps, Ns::Official, &owner_official);
-        let ns = ns_of(target_aw_ns);
-        if after_owner.is_empty() {
-            let owner_named = from_inter_class(maps, ns, &owner_inter);
-            out.push(format!("accessible class {}", owner_named));
-            if owner_named == owner_official {
-                stats.failed += 1;
+        let entry = match parse_at_line(raw) {
+            Some(e) => e,
+            None => {
                 stats
-                    .failures
-                    .push(format!("class owner unmapped {}", owner_official));
-            } else {
-                stats.converted += 1;
+                    .unsupported
+                    .push(format!("unparseable AT line: {}", raw));
+                continue;
+            }
+        };
+        let kind = match entry.target {
+            AtTarget::Class => EntryKind::Class,
+            AtTarget::Field(_) | AtTarget::AllFields => EntryKind::Field,
+            AtTarget::Method(_, _) | AtTarget::AllMethods => EntryKind::Method,
+        };
+        let accesses = match at_to_aw_accesses(entry.modifier, kind) {
+            Ok((a, note)) => {
+                if let Some(note) = note {
+                    stats.approximated.push(format!("{}: {}", note, raw));
+                }
+                a
+            }
+            Err(reason) => {
+                stats.unsupported.push(format!("{}: {}", reason, raw));
+                continue;
+            }
+        };
+        let comment = entry
+            .comment
+            .as_ref()
+            .map(|c| format!(" # {}", c))
+            .unwrap_or_default();
+        let owner_at = entry.owner.clone();
+        let owner_inter = to_inter_class(maps, at_ns, &owner_at);
+        let owner_named = from_inter_class(maps, ns, &owner_inter);
+        let members: Vec<(String, String)> = match &entry.target {
+            AtTarget::Class => {
+                for access in &accesses {
+                    out.push(format!(
+                        "{} class {}{}",
+                        aw_access_str(*access, false),
+                        owner_named,
+                        comment
+                    ));
+                }
+                if owner_named == owner_at {
+                    stats.failed += 1;
+                    stats
+                        .failures
+                        .push(format!("class owner unmapped {}", owner_at));
+                } else {
+                    stats.converted += 1;
+                }
+                continue;
+            }
+            AtTarget::Field(name) => {
+                let member_inter = to_inter_field(maps, at_ns, &owner_inter, name);
+                let desc_inter = field_desc_inter(maps, &owner_inter, &member_inter);
+                vec![(member_inter, desc_inter)]
             }
+            AtTarget::Method(name, desc) => vec![(
+                to_inter_method(maps, at_ns, &owner_inter, name, desc),
+                translate_desc(maps, at_ns, Ns::Intermediary, desc),
+            )],
+            AtTarget::AllFields => members_of(maps, &owner_inter, false),
+            AtTarget::AllMethods => members_of(maps, &owner_inter, true),
+        };
+        if members.is_empty() {
+            stats.unsupported.push(format!(
+                "wildcard could not be expanded, no mapped members of {}: {}",
+                owner_at, raw
+            ));
             continue;
         }
-        if after_owner.contains('(') {
-            let mut name = String::new();
-            let mut desc = String::new();
-            if let Some(idx) = after_owner.find('(') {
-                name = after_owner[..idx].to_string();
-                desc = after_owner[idx..].to_string();
+        for (member_inter, desc_inter) in members {
+            if kind == EntryKind::Method {
+                if desc_inter.is_empty() {
+                    stats.unsupported.push(format!(
+                        "method descriptor unknown for {}::{}",
+                        owner_at, member_inter
+                    ));
+                    continue;
+                }
+                let aw_name = from_inter_method(maps, ns, &owner_inter, &member_inter, &desc_inter);
+                let aw_desc = translate_desc(maps, Ns::Intermediary, ns, &desc_inter);
+                for access in &accesses {
+                    out.push(format!(
+                        "{} method {} {} {}{}",
+                        aw_access_str(*access, false),
+                        owner_named,
+                        aw_name,
+                        aw_desc,
+                        comment
+                    ));
+                }
+                let fallback = aw_name == member_inter && owner_named == owner_at;
+                if fallback {
+                    stats.failed += 1;
+                    stats
+                        .failures
+                        .push(format!("method unmapped {}::{}", owner_at, member_inter));
+                } else {
+                    stats.converted += 1;
+                }
             } else {
-                name = after_owner.to_string();
-            }
-            let member_inter = name.clone();
-            let aw_name = from_inter_member(maps, ns, &owner_inter, &member_inter, true);
-            let aw_desc = match ns {
-                Ns::Yarn => convert_desc_adv(
-                    &desc,
-                    &maps.class_maps.moj_named_to_inter,
-                    &maps.class_maps.yarn_inter_to_named,
-                ),
-                Ns::Official => desc.clone(),
-            };
-            let owner_named = from_inter_class(maps, ns, &owner_inter);
-            out.push(format!(
-                "accessible method {} {} {}",
-                owner_named, aw_name, aw_desc
-            ));
-            let fallback = aw_name == member_inter && owner_named == owner_official;
-            if fallback {
-                stats.failed += 1;
-                stats.failures.push(format!(
-                    "method unmapped {}::{}",
-                    owner_official, member_inter
-                ));
-            } else {
-                stats.converted += 1;
-            }
-        } else {
-            let member_inter = after_owner.to_string();
-            let aw_name = from_inter_member(maps, ns, &owner_inter, &member_inter, false);
-            let owner_named = from_inter_class(maps, ns, &owner_inter);
-            out.push(format!("accessible field {} {}", owner_named, aw_name));
-            let fallback = aw_name == member_inter && owner_named == owner_official;
-            if fallback {
-                stats.failed += 1;
-                stats.failures.push(format!(
-                    "field unmapped {}::{}",
-                    owner_official, member_inter
-                ));
-            } else {
-                stats.converted += 1;
+                let aw_name = from_inter_field(maps, ns, &owner_inter, &member_inter);
+                let aw_desc = translate_desc(maps, Ns::Intermediary, ns, &desc_inter);
+                let member = if aw_desc.is_empty() {
+                    aw_name.clone()
+                } else {
+                    format!("{} {}", aw_name, aw_desc)
+                };
+                for access in &accesses {
+                    out.push(format!(
+                        "{} field {} {}{}",
+                        aw_access_str(*access, false),
+                        owner_named,
+                        member,
+                        comment
+                    ));
+                }
+                let fallback = aw_name == member_inter && owner_named == owner_at;
+                if fallback {
+                    stats.failed += 1;
+                    stats
+                        .failures
+                        .push(format!("field unmapped {}::{}", owner_at, member_inter));
+                } else {
+                    stats.converted += 1;
+                }
             }
         }
     }
+    stats.close_line(&out);
     (out, stats)
 }
 
+fn class_exists(maps: &Maps, inter: &str) -> bool {
+    maps.tables
+        .values()
+        .any(|t| t.class_from_inter.contains_key(inter))
+        || maps.methods_by_class.contains_key(inter)
+        || maps.fields_by_class.contains_key(inter)
+}
+
+fn member_exists(
+    maps: &Maps,
+    owner_inter: &str,
+    member_inter: &str,
+    desc_inter: &str,
+    is_method: bool,
+) -> bool {
+    let by_class = if is_method {
+        &maps.methods_by_class
+    } else {
+        &maps.fields_by_class
+    };
+    by_class
+        .get(owner_inter)
+        .map(|set| {
+            set.iter().any(|(n, d)| {
+                n == member_inter && (d.is_empty() || desc_inter.is_empty() || d == desc_inter)
+            })
+        })
+        .unwrap_or(false)
+}
+
+// 跨版本时按 intermediary 判断：源版本找不到算未映射，目标版本找不到算已移除
+enum PortCheck {
+    Ok,
+    Unmapped,
+    Missing,
+}
+
+fn port_check(
+    in_maps: &Maps,
+    out_maps: &Maps,
+    owner_inter: &str,
+    member: Option<(&str, &str, bool)>,
+) -> PortCheck {
+    let exists = |maps: &Maps| {
+        class_exists(maps, owner_inter)
+            && member
+                .map(|(name, desc, is_method)| {
+                    member_exists(maps, owner_inter, name, desc, is_method)
+                })
+                .unwrap_or(true)
+    };
+    if !exists(in_maps) {
+        PortCheck::Unmapped
+    } else if !exists(out_maps) {
+        PortCheck::Missing
+    } else {
+        PortCheck::Ok
+    }
+}
+
+fn flag_missing(out: &mut Vec<String>, stats: &mut Stats, target_version: &str, raw: &str) {
+    out.push(format!("# MISSING in {}: {}", target_version, raw));
+    stats
+        .missing
+        .push(format!("{} no longer exists in {}", raw, target_version));
+}
+
 fn aw_to_aw_lines(
     lines: &[String],
-    maps: &Maps,
-    input_ns: &str,
-    output_ns: &str,
+    in_maps: &Maps,
+    out_maps: &Maps,
+    in_ns_e: Ns,
+    out_ns_e: Ns,
+    target_version: Option<&str>,
 ) -> (Vec<String>, Stats) {
     let mut out: Vec<String> = Vec::new();
-    let mut stats = Stats {
-        converted: 0,
-        failed: 0,
-        failures: Vec::new(),
-    };
+    let mut stats = Stats::default();
     out.extend(disclaimer_lines());
-    out.push(build_aw_header(output_ns));
-    for line in lines {
+    out.push(build_aw_header());
+    for (i, line) in lines.iter().enumerate() {
+        stats.open_line(&out, i + 1, line);
         let raw = line.trim();
+        if is_disclaimer_line(raw) || is_aw_header_line(raw) {
+            continue;
+        }
         if raw.is_empty() || raw.starts_with('#') {
             out.push(line.clone());
             continue;
@@ -656,15 +1715,29 @@ fn aw_to_aw_lines(
         if parsed.is_none() {
             continue;
         }
-        let (_access, typ, rest) = parsed.unwrap();
-        let in_ns_e = ns_of(input_ns);
-        let out_ns_e = ns_of(output_ns);
+        let (access_s, typ, rest) = parsed.unwrap();
+        let access = match (parse_aw_access(&access_s), entry_kind_of(&typ)) {
+            (Some((access, transitive)), Some(_)) => aw_access_str(access, transitive),
+            _ => {
+                stats.unsupported.push(format!("unknown AW entry: {}", raw));
+                continue;
+            }
+        };
         if typ == "class" {
             let owner_in_named = rest.get(0).cloned().unwrap_or_default();
-            let owner_inter = to_inter_class(maps, in_ns_e, &owner_in_named);
-            let owner_out_named = from_inter_class(maps, out_ns_e, &owner_inter);
-            out.push(format!("accessible class {}", owner_out_named));
-            if owner_out_named == owner_in_named {
+            let owner_inter = to_inter_class(in_maps, in_ns_e, &owner_in_named);
+            let check = target_version.map(|_| port_check(in_maps, out_maps, &owner_inter, None));
+            if let (Some(v), Some(PortCheck::Missing)) = (target_version, &check) {
+                flag_missing(&mut out, &mut stats, v, raw);
+                continue;
+            }
+            let owner_out_named = from_inter_class(out_maps, out_ns_e, &owner_inter);
+            out.push(format!("{} class {}", access, owner_out_named));
+            let fallback = match check {
+                Some(c) => matches!(c, PortCheck::Unmapped),
+                None => owner_out_named == owner_in_named,
+            };
+            if fallback {
                 stats.failed += 1;
                 stats
                     .failures
@@ -681,47 +1754,52 @@ fn aw_to_aw_lines(
             if rest.len() < 2 && typ == "field" {
                 continue;
             }
+            let is_method = typ == "method";
             let owner_in_named = rest.get(0).cloned().unwrap_or_default();
             let name_in = rest.get(1).cloned().unwrap_or_default();
-            let desc_in = if typ == "method" {
-                rest.get(2).cloned().unwrap_or_default()
+            let desc_in = rest.get(2).cloned().unwrap_or_default();
+            let owner_inter = to_inter_class(in_maps, in_ns_e, &owner_in_named);
+            let desc_inter = translate_desc(in_maps, in_ns_e, Ns::Intermediary, &desc_in);
+            let name_inter = if is_method {
+                to_inter_method(in_maps, in_ns_e, &owner_inter, &name_in, &desc_in)
             } else {
-                "".to_string()
+                to_inter_field(in_maps, in_ns_e, &owner_inter, &name_in)
             };
-            let owner_inter = to_inter_class(maps, in_ns_e, &owner_in_named);
-            let name_inter = if typ == "method" {
-                to_inter_member(maps, in_ns_e, &owner_inter, &name_in, true)
+            let check = target_version.map(|_| {
+                port_check(
+                    in_maps,
+                    out_maps,
+                    &owner_inter,
+                    Some((&name_inter, &desc_inter, is_method)),
+                )
+            });
+            if let (Some(v), Some(PortCheck::Missing)) = (target_version, &check) {
+                flag_missing(&mut out, &mut stats, v, raw);
+                continue;
+            }
+            let owner_out_named = from_inter_class(out_maps, out_ns_e, &owner_inter);
+            let desc_out = if desc_inter.is_empty() {
+                String::new()
             } else {
-                to_inter_member(maps, in_ns_e, &owner_inter, &name_in, false)
+                translate_desc(out_maps, Ns::Intermediary, out_ns_e, &desc_inter)
             };
-            let owner_out_named = from_inter_class(maps, out_ns_e, &owner_inter);
-            let name_out = if typ == "method" {
-                from_inter_member(maps, out_ns_e, &owner_inter, &name_inter, true)
+            let name_out = if is_method {
+                from_inter_method(out_maps, out_ns_e, &owner_inter, &name_inter, &desc_inter)
             } else {
-                from_inter_member(maps, out_ns_e, &owner_inter, &name_inter, false)
+                from_inter_field(out_maps, out_ns_e, &owner_inter, &name_inter)
             };
-            if typ == "method" {
-                let desc_out = match (in_ns_e, out_ns_e) {
-                    (Ns::Yarn, Ns::Official) => convert_desc_adv(
-                        &desc_in,
-                        &maps.class_maps.yarn_named_to_inter,
-                        &maps.class_maps.moj_inter_to_named,
-                    ),
-                    (Ns::Official, Ns::Yarn) => convert_desc_adv(
-                        &desc_in,
-                        &maps.class_maps.moj_named_to_inter,
-                        &maps.class_maps.yarn_inter_to_named,
-                    ),
-                    _ => desc_in.clone(),
-                };
+            if is_method || !desc_out.is_empty() {
                 out.push(format!(
-                    "accessible method {} {} {}",
-                    owner_out_named, name_out, desc_out
+                    "{} {} {} {} {}",
+                    access, typ, owner_out_named, name_out, desc_out
                 ));
             } else {
-                out.push(format!("accessible field {} {}", owner_out_named, name_out));
+                out.push(format!("{} field {} {}", access, owner_out_named, name_out));
             }
-            let fallback = owner_out_named == owner_in_named && name_out == name_in;
+            let fallback = match check {
+                Some(c) => matches!(c, PortCheck::Unmapped),
+                None => owner_out_named == owner_in_named && name_out == name_in,
+            };
             if fallback {
                 stats.failed += 1;
                 stats
@@ -732,6 +1810,93 @@ fn aw_to_aw_lines(
             }
         }
     }
+    stats.close_line(&out);
+    (out, stats)
+}
+
+fn at_to_at_lines(
+    lines: &[String],
+    in_maps: &Maps,
+    out_maps: &Maps,
+    in_ns: Ns,
+    out_ns: Ns,
+    target_version: Option<&str>,
+) -> (Vec<String>, Stats) {
+    let mut out: Vec<String> = Vec::new();
+    let mut stats = Stats::default();
+    out.extend(disclaimer_lines());
+    for (i, line) in lines.iter().enumerate() {
+        stats.open_line(&out, i + 1, line);
+        let raw = line.trim();
+        if is_disclaimer_line(raw) || is_aw_header_line(raw) {
+            continue;
+        }
+        if raw.is_empty() || raw.starts_with('#') {
+            out.push(line.clone());
+            continue;
+        }
+        let entry = match parse_at_line(raw) {
+            Some(e) => e,
+            None => {
+                stats
+                    .unsupported
+                    .push(format!("unparseable AT line: {}", raw));
+                continue;
+            }
+        };
+        let comment = entry
+            .comment
+            .as_ref()
+            .map(|c| format!(" # {}", c))
+            .unwrap_or_default();
+        let owner_inter = to_inter_class(in_maps, in_ns, &entry.owner);
+        let owner_out = from_inter_class(out_maps, out_ns, &owner_inter);
+        let (line_out, member) = match &entry.target {
+            AtTarget::Class => (build_at_line_class(entry.modifier, &owner_out), None),
+            AtTarget::AllFields => (build_at_line_field(entry.modifier, &owner_out, "*"), None),
+            AtTarget::AllMethods => (build_at_line_field(entry.modifier, &owner_out, "*()"), None),
+            AtTarget::Field(name) => {
+                let inter = to_inter_field(in_maps, in_ns, &owner_inter, name);
+                let name_out = from_inter_field(out_maps, out_ns, &owner_inter, &inter);
+                (
+                    build_at_line_field(entry.modifier, &owner_out, &name_out),
+                    Some((inter, String::new(), false)),
+                )
+            }
+            AtTarget::Method(name, desc) => {
+                let desc_inter = translate_desc(in_maps, in_ns, Ns::Intermediary, desc);
+                let inter = to_inter_method(in_maps, in_ns, &owner_inter, name, desc);
+                let name_out =
+                    from_inter_method(out_maps, out_ns, &owner_inter, &inter, &desc_inter);
+                let desc_out = translate_desc(out_maps, Ns::Intermediary, out_ns, &desc_inter);
+                (
+                    build_at_line_method(entry.modifier, &owner_out, &name_out, &desc_out),
+                    Some((inter, desc_inter, true)),
+                )
+            }
+        };
+        let check = port_check(
+            in_maps,
+            out_maps,
+            &owner_inter,
+            member
+                .as_ref()
+                .map(|(name, desc, is_method)| (name.as_str(), desc.as_str(), *is_method)),
+        );
+        match (check, target_version) {
+            (PortCheck::Missing, Some(v)) => {
+                flag_missing(&mut out, &mut stats, v, raw);
+                continue;
+            }
+            (PortCheck::Unmapped, _) => {
+                stats.failed += 1;
+                stats.failures.push(format!("entry unmapped {}", raw));
+            }
+            _ => stats.converted += 1,
+        }
+        out.push(format!("{}{}", line_out, comment));
+    }
+    stats.close_line(&out);
     (out, stats)
 }
 
@@ -740,86 +1905,1962 @@ fn read_lines(path: &Path) -> anyhow::Result<Vec<String>> {
     Ok(content.lines().map(|s| s.to_string()).collect())
 }
 
+fn suffixed_path(path: &Path) -> std::path::PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 1;
+    loop {
+        let candidate = path.with_file_name(format!("{}-{}{}", stem, n, ext));
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MergeAccess {
+    At(AtModifier),
+    // AW 的访问类型是键的一部分，只有 transitive 可以放宽
+    Aw(AwAccess, bool),
+}
+
+struct MergeEntry {
+    sort_key: (String, u8, String),
+    access: MergeAccess,
+    text: String,
+    comments: Vec<String>,
+}
+
+fn merge_key_of(raw: &str, is_aw: bool) -> Option<(String, (String, u8, String), MergeAccess)> {
+    if is_aw {
+        let (access_s, typ, rest) = parse_aw_line(raw)?;
+        let (access, transitive) = parse_aw_access(&access_s)?;
+        let rank = match entry_kind_of(&typ)? {
+            EntryKind::Class => 0,
+            EntryKind::Field => 1,
+            EntryKind::Method => 2,
+        };
+        let owner = rest.first().cloned().unwrap_or_default();
+        let member = rest.get(1..).map(|r| r.join(" ")).unwrap_or_default();
+        let identity = format!(
+            "{} {} {}",
+            aw_access_str(access, false),
+            typ,
+            rest.join(" ")
+        );
+        let sort_key = (
+            owner,
+            rank,
+            format!("{} {}", member, aw_access_str(access, false)),
+        );
+        Some((identity, sort_key, MergeAccess::Aw(access, transitive)))
+    } else {
+        let entry = parse_at_line(raw)?;
+        let (rank, member) = match &entry.target {
+            AtTarget::Class => (0, String::new()),
+            AtTarget::AllFields => (1, "*".to_string()),
+            AtTarget::Field(name) => (2, name.clone()),
+            AtTarget::AllMethods => (3, "*()".to_string()),
+            AtTarget::Method(name, desc) => (4, format!("{}{}", name, desc)),
+        };
+        let identity = format!("{} {}", entry.owner, member);
+        Some((
+            identity,
+            (entry.owner, rank, member),
+            MergeAccess::At(entry.modifier),
+        ))
+    }
+}
+
+// 取两者中更宽松的访问：可见性取高，final 以移除优先
+fn widen_access(a: MergeAccess, b: MergeAccess) -> MergeAccess {
+    match (a, b) {
+        (MergeAccess::At(x), MergeAccess::At(y)) => {
+            let final_mod = match (x.final_mod, y.final_mod) {
+                (AtFinal::Remove, _) | (_, AtFinal::Remove) => AtFinal::Remove,
+                (AtFinal::Keep, _) | (_, AtFinal::Keep) => AtFinal::Keep,
+                _ => AtFinal::Add,
+            };
+            MergeAccess::At(AtModifier {
+                visibility: x.visibility.max(y.visibility),
+                final_mod,
+            })
+        }
+        (MergeAccess::Aw(access, t1), MergeAccess::Aw(_, t2)) => MergeAccess::Aw(access, t1 || t2),
+        (a, _) => a,
+    }
+}
+
+fn replace_access_token(line: &str, access: MergeAccess) -> String {
+    let token = match access {
+        MergeAccess::At(m) => at_modifier_str(m),
+        MergeAccess::Aw(a, transitive) => aw_access_str(a, transitive),
+    };
+    let trimmed = line.trim_start();
+    match trimmed.find(char::is_whitespace) {
+        Some(i) => format!("{}{}", token, &trimmed[i..]),
+        None => token,
+    }
+}
+
+// 合并到已有文件：只加入新条目，重复条目取更宽的访问，排序并保留注释
+fn merge_into_existing(existing: Vec<String>, new_lines: Vec<String>, is_aw: bool) -> Vec<String> {
+    let mut header: Option<String> = None;
+    let mut top: Vec<String> = Vec::new();
+    let mut entries: Vec<MergeEntry> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut trailing: Vec<String> = Vec::new();
+    for (from_new, lines) in [(false, existing), (true, new_lines)] {
+        let mut pending: Vec<String> = Vec::new();
+        for line in lines {
+            let t = line.trim();
+            if is_aw_header_line(t) {
+                if header.is_none() {
+                    header = Some(line.trim().to_string());
+                }
+                continue;
+            }
+            if from_new && (t.is_empty() || is_disclaimer_line(t)) {
+                continue;
+            }
+            if entries.is_empty() && !from_new {
+                match merge_key_of(t, is_aw) {
+                    Some(_) => {}
+                    None => {
+                        top.push(line);
+                        continue;
+                    }
+                }
+            }
+            if t.is_empty() {
+                continue;
+            }
+            let (identity, sort_key, access) = match merge_key_of(t, is_aw) {
+                Some(k) => k,
+                None => {
+                    pending.push(line);
+                    continue;
+                }
+            };
+            match index.get(&identity) {
+                Some(&i) => {
+                    let merged = widen_access(entries[i].access, access);
+                    if merged != entries[i].access {
+                        entries[i].text = replace_access_token(&entries[i].text, merged);
+                        entries[i].access = merged;
+                    }
+                    // 保留重复条目前的注释，已存在的同一注释不再追加
+                    for c in std::mem::take(&mut pending) {
+                        if !entries[i].comments.iter().any(|l| l.trim() == c.trim()) {
+                            entries[i].comments.push(c);
+                        }
+                    }
+                }
+                None => {
+                    index.insert(identity, entries.len());
+                    entries.push(MergeEntry {
+                        sort_key,
+                        access,
+                        text: line.trim().to_string(),
+                        comments: std::mem::take(&mut pending),
+                    });
+                }
+            }
+        }
+        for c in pending {
+            if !trailing.iter().any(|l| l.trim() == c.trim()) {
+                trailing.push(c);
+            }
+        }
+    }
+    while top.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+        top.pop();
+    }
+    if !top.is_empty() {
+        top.push(String::new());
+    }
+    entries.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
+    let mut out: Vec<String> = Vec::new();
+    if is_aw {
+        out.push(header.unwrap_or_else(build_aw_header));
+    }
+    out.extend(top);
+    for e in entries {
+        out.extend(e.comments);
+        out.push(e.text);
+    }
+    out.extend(trailing);
+    out
+}
+
+// 目标文件已存在时按策略决定最终路径与内容
+fn resolve_output(
+    policy: ConflictPolicy,
+    path: &Path,
+    lines: Vec<String>,
+    is_aw: bool,
+) -> anyhow::Result<(std::path::PathBuf, Vec<String>)> {
+    if !path.exists() {
+        return Ok((path.to_path_buf(), lines));
+    }
+    match policy {
+        ConflictPolicy::Overwrite => Ok((path.to_path_buf(), lines)),
+        ConflictPolicy::Fail => Err(anyhow::anyhow!(
+            "Output file already exists: {}",
+            path.to_string_lossy()
+        )),
+        ConflictPolicy::Suffix => Ok((suffixed_path(path), lines)),
+        ConflictPolicy::Merge => Ok((
+            path.to_path_buf(),
+            merge_into_existing(read_lines(path)?, lines, is_aw),
+        )),
+    }
+}
+
+// 报告写在输出文件旁边，便于逐行定位失败条目
+fn write_report(
+    input_path: &str,
+    out_path: &Path,
+    result: &Value,
+) -> anyhow::Result<std::path::PathBuf> {
+    let report_path =
+        std::path::PathBuf::from(format!("{}.report.json", out_path.to_string_lossy()));
+    let mut report = result.clone();
+    report["inputPath"] = json!(input_path);
+    std::fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+    Ok(report_path)
+}
+
 fn write_lines(path: &Path, lines: &[String]) -> anyhow::Result<()> {
-    let mut out = String::new();
+    std::fs::write(path, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn simple_name(s: &str) -> &str {
+    s.rsplit(['/', '$']).next().unwrap_or(s)
+}
+
+// 按编辑距离取最接近的名称，同名不同包的类也算近似
+fn near_misses(target: &str, candidates: impl Iterator<Item = String>) -> Vec<String> {
+    let limit = (target.len() / 3).max(2);
+    let mut scored: Vec<(usize, String)> = candidates
+        .filter_map(|c| {
+            let d = if simple_name(&c) == simple_name(target) && c != target {
+                0
+            } else {
+                edit_distance(&target.to_lowercase(), &c.to_lowercase())
+            };
+            (d <= limit).then_some((d, c))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored.into_iter().take(5).map(|(_, c)| c).collect()
+}
+
+fn class_candidates(maps: &Maps, ns: Ns) -> Vec<String> {
+    if ns == Ns::Intermediary {
+        let mut out: BTreeSet<String> = maps.methods_by_class.keys().cloned().collect();
+        out.extend(maps.fields_by_class.keys().cloned());
+        for t in maps.tables.values() {
+            out.extend(t.class_from_inter.keys().cloned());
+        }
+        return out.into_iter().collect();
+    }
+    maps.tables
+        .get(&ns)
+        .map(|t| t.class_to_inter.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+// 成员在 ns 下的名称及 intermediary 描述符
+fn member_candidates(
+    maps: &Maps,
+    ns: Ns,
+    owner_inter: &str,
+    is_method: bool,
+) -> Vec<(String, String)> {
+    if ns == Ns::Intermediary {
+        return members_of(maps, owner_inter, is_method);
+    }
+    let t = match maps.tables.get(&ns) {
+        Some(t) => t,
+        None => return Vec::new(),
+    };
+    if is_method {
+        t.method_from_inter
+            .get(owner_inter)
+            .map(|m| {
+                m.iter()
+                    .map(|(key, named)| {
+                        let desc = key.find('(').map(|i| &key[i..]).unwrap_or("");
+                        (named.clone(), desc.to_string())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        t.field_from_inter
+            .get(owner_inter)
+            .map(|m| {
+                m.iter()
+                    .map(|(inter, named)| {
+                        (named.clone(), field_desc_inter(maps, owner_inter, inter))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+struct ValidationIssue {
+    message: String,
+    suggestions: Vec<String>,
+}
+
+fn validate_class(maps: &Maps, ns: Ns, owner: &str) -> Result<String, ValidationIssue> {
+    let inter = if ns == Ns::Intermediary {
+        Some(owner.to_string()).filter(|o| class_exists(maps, o))
+    } else {
+        class_to_inter_opt(maps, ns, owner)
+    };
+    inter.ok_or_else(|| ValidationIssue {
+        message: format!("class {} not found", owner),
+        suggestions: near_misses(owner, class_candidates(maps, ns).into_iter()),
+    })
+}
+
+fn validate_member(
+    maps: &Maps,
+    ns: Ns,
+    owner: &str,
+    name: &str,
+    desc: &str,
+    is_method: bool,
+) -> Result<(), ValidationIssue> {
+    let owner_inter = validate_class(maps, ns, owner)?;
+    let kind = if is_method { "method" } else { "field" };
+    let desc_inter = translate_desc(maps, ns, Ns::Intermediary, desc);
+    let candidates = member_candidates(maps, ns, &owner_inter, is_method);
+    let same_name: Vec<&(String, String)> = candidates.iter().filter(|(n, _)| n == name).collect();
+    if same_name
+        .iter()
+        .any(|(_, d)| desc.is_empty() || d.is_empty() || *d == desc_inter)
+    {
+        return Ok(());
+    }
+    if !same_name.is_empty() {
+        let mut suggestions: Vec<String> = same_name
+            .iter()
+            .map(|(n, d)| format!("{} {}", n, translate_desc(maps, Ns::Intermediary, ns, d)))
+            .collect();
+        suggestions.sort();
+        return Err(ValidationIssue {
+            message: format!("{} {}::{} has no descriptor {}", kind, owner, name, desc),
+            suggestions,
+        });
+    }
+    Err(ValidationIssue {
+        message: format!("{} {}::{} not found", kind, owner, name),
+        suggestions: near_misses(name, candidates.into_iter().map(|(n, _)| n)),
+    })
+}
+
+fn validate_aw_entry(maps: &Maps, ns: Ns, raw: &str) -> Result<(), ValidationIssue> {
+    let invalid = |message: String| ValidationIssue {
+        message,
+        suggestions: Vec::new(),
+    };
+    let (access_s, typ, rest) =
+        parse_aw_line(raw).ok_or_else(|| invalid("unparseable AW line".to_string()))?;
+    if parse_aw_access(&access_s).is_none() {
+        return Err(invalid(format!("unknown access {}", access_s)));
+    }
+    let owner = rest.first().cloned().unwrap_or_default();
+    match entry_kind_of(&typ) {
+        Some(EntryKind::Class) => validate_class(maps, ns, &owner).map(|_| ()),
+        Some(EntryKind::Method) if rest.len() >= 3 => {
+            validate_member(maps, ns, &owner, &rest[1], &rest[2], true)
+        }
+        Some(EntryKind::Field) if rest.len() >= 2 => validate_member(
+            maps,
+            ns,
+            &owner,
+            &rest[1],
+            rest.get(2).map(|s| s.as_str()).unwrap_or(""),
+            false,
+        ),
+        Some(_) => Err(invalid(format!("incomplete {} entry", typ))),
+        None => Err(invalid(format!("unknown entry type {}", typ))),
+    }
+}
+
+fn validate_at_entry(maps: &Maps, ns: Ns, raw: &str) -> Result<(), ValidationIssue> {
+    let entry = parse_at_line(raw).ok_or_else(|| ValidationIssue {
+        message: "unparseable AT line".to_string(),
+        suggestions: Vec::new(),
+    })?;
+    match &entry.target {
+        AtTarget::Class | AtTarget::AllFields | AtTarget::AllMethods => {
+            validate_class(maps, ns, &entry.owner).map(|_| ())
+        }
+        AtTarget::Field(name) => validate_member(maps, ns, &entry.owner, name, "", false),
+        AtTarget::Method(name, desc) => validate_member(maps, ns, &entry.owner, name, desc, true),
+    }
+}
+
+#[tauri::command]
+pub async fn validate_aw_at(
+    input_path: String,
+    mc_version: Option<String>,
+    kind: Option<String>,
+    mapping: Option<String>,
+    options: Option<ConvertOptions>,
+) -> Result<Value, String> {
+    let options = options.unwrap_or_default();
+    let p = Path::new(&input_path);
+    if !p.exists() {
+        return Err(format!("Input file not found: {}", input_path));
+    }
+    let lines = read_lines(p).map_err(|e| e.to_string())?;
+    let is_aw = match kind.as_deref().map(|k| k.to_lowercase()) {
+        Some(k) if k == "aw" => true,
+        Some(k) if k == "at" => false,
+        Some(k) => return Err(format!("Unknown file kind: {}", k)),
+        None => lines
+            .iter()
+            .any(|l| l.trim_start().starts_with("accessWidener")),
+    };
+    let mc_version = crate::settings::resolve_mc_version(mc_version);
+    let loader = options
+        .loader
+        .clone()
+        .or(crate::settings::current().default_loader);
+    let ns = match (ns_opt(mapping.as_deref())?, &mc_version) {
+        (Some(m), _) => m,
+        (None, _) if is_aw => Ns::Official,
+        (None, Ok(v)) => default_at_ns(loader.as_deref(), v),
+        (None, Err(_)) => Ns::Official,
+    };
+    let sets = mapping_sets(&[ns]);
+    let maps = load_maps(options.mapping_files.clone(), mc_version, &sets)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut checked = 0;
+    let mut errors = Vec::new();
     for (i, line) in lines.iter().enumerate() {
-        out.push_str(line);
-        if i + 1 < lines.len() {
-            out.push('\n');
+        let raw = line.trim();
+        if raw.is_empty()
+            || raw.starts_with('#')
+            || raw.starts_with("accessWidener")
+            || is_disclaimer_line(raw)
+        {
+            continue;
+        }
+        checked += 1;
+        let result = if is_aw {
+            validate_aw_entry(&maps, ns, raw)
         } else {
-            out.push('\n');
+            validate_at_entry(&maps, ns, raw)
+        };
+        if let Err(issue) = result {
+            errors.push(json!({
+                "line": i + 1,
+                "text": raw,
+                "message": issue.message,
+                "suggestions": issue.suggestions
+            }));
         }
     }
-    std::fs::write(path, out)?;
-    Ok(())
+    crate::util::log_event(
+        "info",
+        &format!(
+            "validate_aw_at {} checked={} errors={}",
+            input_path,
+            checked,
+            errors.len()
+        ),
+    );
+    Ok(json!({
+        "valid": errors.is_empty(),
+        "kind": if is_aw { "aw" } else { "at" },
+        "mapping": ns_name(ns),
+        "checked": checked,
+        "errors": errors
+    }))
 }
 
 #[tauri::command]
 pub async fn convert_aw_at(
     input_path: String,
-    mc_version: String,
+    mc_version: Option<String>,
     direction: String,
     input_mapping: Option<String>,
     output_mapping: Option<String>,
     aw_output_name: Option<String>,
+    options: Option<ConvertOptions>,
 ) -> Result<Value, String> {
-    let maps = prepare_maps(&mc_version).await.map_err(|e| e.to_string())?;
+    let options = options.unwrap_or_default();
+    let policy = conflict_policy_of(options.on_conflict.as_deref()).map_err(|e| e.to_string())?;
+    if options.dry_run && options.write_report {
+        return Err(
+            "writeReport cannot be combined with dryRun; the dry-run result already contains the line report"
+                .to_string(),
+        );
+    }
+    let dir = direction.to_lowercase();
+    let mc_version = crate::settings::resolve_mc_version(mc_version);
+    let loader = options
+        .loader
+        .clone()
+        .or(crate::settings::current().default_loader);
+    let input_ns = ns_opt(input_mapping.as_deref())?;
+    let output_ns = ns_opt(output_mapping.as_deref())?;
+    // AT 一侧的命名空间：aw_to_at 取 output_mapping，at_to_aw 取 input_mapping
+    let at_mapping = match dir.as_str() {
+        "aw_to_at" => output_ns,
+        "at_to_aw" | "at_to_at" => input_ns,
+        _ => None,
+    };
+    let at_ns = match (at_mapping, &mc_version) {
+        (Some(m), _) => m,
+        (None, Ok(v)) => default_at_ns(loader.as_deref(), v),
+        (None, Err(_)) => Ns::Official,
+    };
+    let target_version = options
+        .target_mc_version
+        .clone()
+        .filter(|v| !v.trim().is_empty());
+    if target_version.is_some() && dir != "aw_to_aw" && dir != "at_to_at" {
+        return Err("Cross-version conversion supports aw_to_aw and at_to_at only".to_string());
+    }
+    let out_at_ns = match (output_ns, &target_version, &mc_version) {
+        (Some(m), _, _) => m,
+        (None, Some(v), _) | (None, None, Ok(v)) => default_at_ns(loader.as_deref(), v),
+        _ => Ns::Official,
+    };
+    let mut used = vec![at_ns];
+    if dir == "at_to_at" {
+        used.push(out_at_ns);
+    }
+    used.extend(input_ns.into_iter().chain(output_ns));
+    let sets = mapping_sets(&used);
+    let maps = load_maps(options.mapping_files.clone(), mc_version, &sets)
+        .await
+        .map_err(|e| e.to_string())?;
+    let target_maps = match &target_version {
+        Some(v) => Some(prepare_maps(v, &sets).await.map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let out_maps = target_maps.as_ref().unwrap_or(&maps);
     let p = Path::new(&input_path);
     if !p.exists() {
         return Err(format!("Input file not found: {}", input_path));
     }
-    let mut lines = read_lines(p).map_err(|e| e.to_string())?;
-    let dir = direction.to_lowercase();
-    if dir == "aw_to_at" {
-        lines = sanitize_aw_input_lines(&lines);
-        let ns = input_mapping
-            .clone()
-            .unwrap_or_else(|| "official".to_string());
-        let (out_lines, stats) = aw_to_at_lines(&lines, &maps, &ns);
-        let out_path = p
-            .parent()
-            .unwrap_or_else(|| Path::new("."))
-            .join("accesstransformer.cfg");
-        write_lines(&out_path, &out_lines).map_err(|e| e.to_string())?;
-        return Ok(json!({
-            "outputPath": out_path.to_string_lossy(),
-            "converted": stats.converted,
-            "failed": stats.failed,
-            "failures": stats.failures
-        }));
-    } else if dir == "at_to_aw" {
-        lines = sanitize_aw_input_lines(&lines);
-        let ns = output_mapping
-            .clone()
-            .unwrap_or_else(|| "official".to_string());
-        let (out_lines, stats) = at_to_aw_lines(&lines, &maps, &ns);
-        let out_name = aw_output_name.unwrap_or_else(|| "converted.accesswidener".to_string());
-        let out_path = p.parent().unwrap_or_else(|| Path::new(".")).join(out_name);
-        write_lines(&out_path, &out_lines).map_err(|e| e.to_string())?;
-        return Ok(json!({
-            "outputPath": out_path.to_string_lossy(),
-            "converted": stats.converted,
-            "failed": stats.failed,
-            "failures": stats.failures
-        }));
-    } else if dir == "aw_to_aw" {
-        lines = sanitize_aw_input_lines(&lines);
-        let in_ns = input_mapping
-            .clone()
-            .unwrap_or_else(|| "official".to_string());
-        let out_ns = output_mapping
-            .clone()
-            .unwrap_or_else(|| "official".to_string());
-        let (out_lines, stats) = aw_to_aw_lines(&lines, &maps, &in_ns, &out_ns);
-        let out_name = aw_output_name.unwrap_or_else(|| "converted.accesswidener".to_string());
-        let out_path = p.parent().unwrap_or_else(|| Path::new(".")).join(out_name);
+    let lines = read_lines(p).map_err(|e| e.to_string())?;
+    let parent = p.parent().unwrap_or_else(|| Path::new("."));
+    let (out_lines, stats, out_path, out_at_ns) = match dir.as_str() {
+        "aw_to_at" => {
+            let ns = input_ns.unwrap_or(Ns::Official);
+            let (out_lines, stats) = aw_to_at_lines(&lines, &maps, ns, at_ns);
+            let out_path = parent.join("accesstransformer.cfg");
+            (out_lines, stats, out_path, Some(at_ns))
+        }
+        "at_to_aw" => {
+            let ns = output_ns.unwrap_or(Ns::Official);
+            let (out_lines, stats) = at_to_aw_lines(&lines, &maps, ns, at_ns);
+            let out_name = aw_output_name.unwrap_or_else(|| "converted.accesswidener".to_string());
+            (out_lines, stats, parent.join(out_name), None)
+        }
+        "aw_to_aw" => {
+            let (out_lines, stats) = aw_to_aw_lines(
+                &lines,
+                &maps,
+                out_maps,
+                input_ns.unwrap_or(Ns::Official),
+                output_ns.unwrap_or(Ns::Official),
+                target_version.as_deref(),
+            );
+            let out_name = aw_output_name.unwrap_or_else(|| "converted.accesswidener".to_string());
+            (out_lines, stats, parent.join(out_name), None)
+        }
+        "at_to_at" => {
+            let (out_lines, stats) = at_to_at_lines(
+                &lines,
+                &maps,
+                out_maps,
+                at_ns,
+                out_at_ns,
+                target_version.as_deref(),
+            );
+            let out_name =
+                aw_output_name.unwrap_or_else(|| "accesstransformer.converted.cfg".to_string());
+            (out_lines, stats, parent.join(out_name), Some(out_at_ns))
+        }
+        _ => return Err("Unsupported direction".to_string()),
+    };
+    let out_path = match options.output_path.as_deref().map(str::trim) {
+        Some(custom) if !custom.is_empty() => parent.join(custom),
+        _ => out_path,
+    };
+    let (out_path, out_lines) = resolve_output(policy, &out_path, out_lines, out_at_ns.is_none())
+        .map_err(|e| e.to_string())?;
+    if !options.dry_run {
         write_lines(&out_path, &out_lines).map_err(|e| e.to_string())?;
-        return Ok(json!({
+    }
+    let mut result = json!({
+        "outputPath": out_path.to_string_lossy(),
+        "written": !options.dry_run,
+        "converted": stats.converted,
+        "failed": stats.failed,
+        "failures": stats.failures,
+        "unsupported": stats.unsupported,
+        "missing": stats.missing,
+        "approximated": stats.approximated,
+        "lines": stats.report
+    });
+    if let Some(ns) = out_at_ns {
+        result["atMapping"] = json!(ns_name(ns));
+    }
+    if options.dry_run {
+        result["content"] = json!(out_lines.join("\n") + "\n");
+    } else if options.write_report {
+        let report_path =
+            write_report(&input_path, &out_path, &result).map_err(|e| e.to_string())?;
+        result["reportPath"] = json!(report_path.to_string_lossy());
+    }
+    Ok(result)
+}
+
+const PROJECT_SKIP_DIRS: &[&str] = &[
+    ".git",
+    ".gradle",
+    ".idea",
+    "build",
+    "bin",
+    "out",
+    "run",
+    "node_modules",
+];
+
+fn collect_project_files(dir: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if depth > 0 && !PROJECT_SKIP_DIRS.contains(&name.as_str()) {
+                collect_project_files(&path, depth - 1, out);
+            }
+            continue;
+        }
+        if matches!(
+            name.as_str(),
+            "build.gradle"
+                | "build.gradle.kts"
+                | "fabric.mod.json"
+                | "mods.toml"
+                | "neoforge.mods.toml"
+                | "accesstransformer.cfg"
+        ) {
+            out.push(path);
+        }
+    }
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn is_meta_inf(dir: &Path) -> bool {
+    dir.file_name().map(|n| n == "META-INF").unwrap_or(false)
+}
+
+#[derive(Default)]
+struct ProjectFiles {
+    access_wideners: Vec<PathBuf>,
+    // (路径, forge / neoforge)
+    access_transformers: Vec<(PathBuf, String)>,
+}
+
+impl ProjectFiles {
+    fn add_aw(&mut self, path: PathBuf) {
+        let path = normalize_path(&path);
+        if !self.access_wideners.contains(&path) {
+            self.access_wideners.push(path);
+        }
+    }
+
+    fn add_at(&mut self, path: PathBuf, loader: &str) {
+        let path = normalize_path(&path);
+        if !self.access_transformers.iter().any(|(p, _)| *p == path) {
+            self.access_transformers.push((path, loader.to_string()));
+        }
+    }
+}
+
+fn discover_project_files(root: &Path) -> ProjectFiles {
+    let mut files = Vec::new();
+    collect_project_files(root, 6, &mut files);
+    files.sort();
+    let mut found = ProjectFiles::default();
+    // 先处理声明，再补上未被任何声明引用的 accesstransformer.cfg
+    for path in &files {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let dir = path.parent().unwrap_or(root);
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        match name.as_ref() {
+            "build.gradle" | "build.gradle.kts" => {
+                for cap in GRADLE_AW_RE.captures_iter(&content) {
+                    found.add_aw(dir.join(&cap[1]));
+                }
+            }
+            "fabric.mod.json" => {
+                let declared = serde_json::from_str::<Value>(&content)
+                    .ok()
+                    .and_then(|v| v["accessWidener"].as_str().map(|s| s.to_string()));
+                if let Some(aw) = declared {
+                    let local = dir.join(&aw);
+                    // Architectury 中 fabric 模块引用的 AW 通常位于 common 的资源目录
+                    let shared = found
+                        .access_wideners
+                        .iter()
+                        .any(|p| p.file_name().map(|n| *n == *aw).unwrap_or(false));
+                    if local.exists() || !shared {
+                        found.add_aw(local);
+                    }
+                }
+            }
+            "neoforge.mods.toml" if is_meta_inf(dir) => {
+                let resources = dir.parent().unwrap_or(dir);
+                let mut declared = false;
+                for cap in TOML_AT_RE.captures_iter(&content) {
+                    found.add_at(resources.join(&cap[1]), "neoforge");
+                    declared = true;
+                }
+                if !declared {
+                    found.add_at(dir.join("accesstransformer.cfg"), "neoforge");
+                }
+            }
+            "mods.toml" if is_meta_inf(dir) => {
+                found.add_at(dir.join("accesstransformer.cfg"), "forge");
+            }
+            _ => {}
+        }
+    }
+    for path in &files {
+        let dir = path.parent().unwrap_or(root);
+        if path
+            .file_name()
+            .map(|n| n == "accesstransformer.cfg")
+            .unwrap_or(false)
+            && is_meta_inf(dir)
+        {
+            let loader = if dir.join("neoforge.mods.toml").exists() {
+                "neoforge"
+            } else {
+                "forge"
+            };
+            found.add_at(path.clone(), loader);
+        }
+    }
+    found
+}
+
+fn path_in_common(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == "common")
+}
+
+#[tauri::command]
+pub async fn convert_project_aw_at(
+    project_dir: String,
+    mc_version: Option<String>,
+    aw_mapping: Option<String>,
+    options: Option<ConvertOptions>,
+) -> Result<Value, String> {
+    let root = Path::new(&project_dir);
+    if !root.is_dir() {
+        return Err(format!("Project directory not found: {}", project_dir));
+    }
+    let mut options = options.unwrap_or_default();
+    // 批量转换默认合并到已有文件，保留手写条目
+    if options.on_conflict.is_none() {
+        options.on_conflict = Some("merge".to_string());
+    }
+    let policy = conflict_policy_of(options.on_conflict.as_deref()).map_err(|e| e.to_string())?;
+    let version = crate::settings::resolve_mc_version(mc_version.clone()).ok();
+    let at_mapping_of = |loader: &str| {
+        version
+            .as_deref()
+            .map(|v| ns_name(default_at_ns(Some(loader), v)).to_string())
+    };
+    let found = discover_project_files(root);
+    let source_aw = found
+        .access_wideners
+        .iter()
+        .filter(|p| p.exists())
+        .min_by_key(|p| !path_in_common(p))
+        .cloned();
+    let source_at = found
+        .access_transformers
+        .iter()
+        .find(|(p, _)| p.exists())
+        .cloned();
+    // (输入, 输出, 方向, 加载器, 输入映射, 输出映射)
+    let mut jobs = Vec::new();
+    if let Some(aw) = &source_aw {
+        for (at, loader) in &found.access_transformers {
+            jobs.push((
+                aw.clone(),
+                at.clone(),
+                "aw_to_at",
+                loader.clone(),
+                aw_mapping.clone(),
+                at_mapping_of(loader),
+            ));
+        }
+    } else if let Some((at, loader)) = &source_at {
+        // 没有 AW 时以现有 AT 为准，补齐缺失的 AW 与其他加载器的 AT
+        for aw in found.access_wideners.iter().filter(|p| !p.exists()) {
+            jobs.push((
+                at.clone(),
+                aw.clone(),
+                "at_to_aw",
+                loader.clone(),
+                at_mapping_of(loader),
+                aw_mapping.clone(),
+            ));
+        }
+        for (other, other_loader) in &found.access_transformers {
+            if !other.exists() {
+                jobs.push((
+                    at.clone(),
+                    other.clone(),
+                    "at_to_at",
+                    other_loader.clone(),
+                    at_mapping_of(loader),
+                    at_mapping_of(other_loader),
+                ));
+            }
+        }
+    }
+    let mut results = Vec::new();
+    let mut failed = 0;
+    for (input, output, direction, loader, input_mapping, output_mapping) in jobs {
+        let overwritten =
+            policy == ConflictPolicy::Overwrite && !options.dry_run && output.exists();
+        let mut job_options = options.clone();
+        job_options.output_path = Some(output.to_string_lossy().to_string());
+        job_options.loader = Some(loader.clone());
+        let res = convert_aw_at(
+            input.to_string_lossy().to_string(),
+            mc_version.clone(),
+            direction.to_string(),
+            input_mapping,
+            output_mapping,
+            None,
+            Some(job_options),
+        )
+        .await;
+        let mut entry = json!({
+            "inputPath": input.to_string_lossy(),
+            "targetPath": output.to_string_lossy(),
+            "direction": direction,
+            "loader": loader,
+            "ok": res.is_ok(),
+            "overwritten": overwritten && res.is_ok()
+        });
+        match res {
+            Ok(v) => entry["result"] = v,
+            Err(e) => {
+                failed += 1;
+                entry["error"] = json!(e);
+            }
+        }
+        results.push(entry);
+    }
+    crate::util::log_event(
+        "info",
+        &format!(
+            "convert_project_aw_at {} jobs={} failed={}",
+            project_dir,
+            results.len(),
+            failed
+        ),
+    );
+    Ok(json!({
+        "accessWideners": found
+            .access_wideners
+            .iter()
+            .map(|p| json!({ "path": p.to_string_lossy(), "exists": p.exists() }))
+            .collect::<Vec<_>>(),
+        "accessTransformers": found
+            .access_transformers
+            .iter()
+            .map(|(p, l)| json!({ "path": p.to_string_lossy(), "loader": l, "exists": p.exists() }))
+            .collect::<Vec<_>>(),
+        "sourcePath": source_aw
+            .or(source_at.map(|(p, _)| p))
+            .map(|p| p.to_string_lossy().to_string()),
+        "results": results,
+        "failed": failed
+    }))
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MixinIssue {
+    file: String,
+    line: usize,
+    kind: &'static str,
+    reference: String,
+}
+
+fn is_game_class(internal: &str) -> bool {
+    internal.starts_with("net/minecraft/") || !internal.contains('/')
+}
+
+fn class_inter_of(maps: &Maps, ns: Ns, internal: &str) -> Option<String> {
+    class_to_inter_opt(maps, ns, internal).filter(|inter| class_exists(maps, inter))
+}
+
+// 点分名可能包含内部类，依次把末尾的 . 换成 $ 尝试
+fn resolve_dotted_class(maps: &Maps, ns: Ns, dotted: &str) -> Option<String> {
+    let mut candidate = dotted.replace('.', "/");
+    loop {
+        if let Some(inter) = class_inter_of(maps, ns, &candidate) {
+            return Some(inter);
+        }
+        let idx = candidate.rfind('/')?;
+        candidate.replace_range(idx..idx + 1, "$");
+    }
+}
+
+fn remap_member_name(
+    maps: &Maps,
+    from: Ns,
+    to: Ns,
+    owner_inter: &str,
+    name: &str,
+    desc_inter: &str,
+    is_method: bool,
+) -> Option<String> {
+    let targets: BTreeSet<String> = members_of(maps, owner_inter, is_method)
+        .into_iter()
+        .filter(|(n, d)| {
+            let named = if is_method {
+                from_inter_method(maps, from, owner_inter, n, d)
+            } else {
+                from_inter_field(maps, from, owner_inter, n)
+            };
+            named == name && (desc_inter.is_empty() || d.is_empty() || d == desc_inter)
+        })
+        .map(|(n, d)| {
+            if is_method {
+                from_inter_method(maps, to, owner_inter, &n, &d)
+            } else {
+                from_inter_field(maps, to, owner_inter, &n)
+            }
+        })
+        .collect();
+    // 未带描述符的选择器匹配到多个重载时，只要目标名一致即可
+    if targets.len() == 1 {
+        targets.into_iter().next()
+    } else {
+        None
+    }
+}
+
+// Lowner;name(desc) 或 Lowner;name:desc
+fn remap_member_ref(maps: &Maps, from: Ns, to: Ns, reference: &str) -> Result<String, String> {
+    let (owner, rest) = reference[1..]
+        .split_once(';')
+        .ok_or_else(|| reference.to_string())?;
+    let owner_inter = match class_inter_of(maps, from, owner) {
+        Some(inter) => inter,
+        None if is_game_class(owner) => return Err(reference.to_string()),
+        None => {
+            return Ok(format!(
+                "L{};{}",
+                owner,
+                translate_desc(maps, from, to, rest)
+            ))
+        }
+    };
+    let owner_out = from_inter_class(maps, to, &owner_inter);
+    let member = remap_selector(maps, from, to, &owner_inter, rest)?;
+    Ok(format!("L{};{}", owner_out, member))
+}
+
+fn remap_selector(
+    maps: &Maps,
+    from: Ns,
+    to: Ns,
+    owner_inter: &str,
+    selector: &str,
+) -> Result<String, String> {
+    let (name, desc, sep, is_method) = match selector.find(['(', ':']) {
+        Some(i) => (
+            &selector[..i],
+            &selector[i + 1..],
+            &selector[i..i + 1],
+            &selector[i..i + 1] == "(",
+        ),
+        None => (selector, "", "", true),
+    };
+    let desc_out = match sep {
+        "(" => translate_desc(maps, from, to, &format!("({}", desc)),
+        ":" => format!(":{}", translate_desc(maps, from, to, desc)),
+        _ => String::new(),
+    };
+    if name.is_empty() || name.starts_with('<') || name.contains('*') {
+        return Ok(format!("{}{}", name, desc_out));
+    }
+    let desc_inter = match sep {
+        "(" => translate_desc(maps, from, Ns::Intermediary, &format!("({}", desc)),
+        ":" => translate_desc(maps, from, Ns::Intermediary, desc),
+        _ => String::new(),
+    };
+    remap_member_name(maps, from, to, owner_inter, name, &desc_inter, is_method)
+        .map(|n| format!("{}{}", n, desc_out))
+        .ok_or_else(|| selector.to_string())
+}
+
+const INJECTOR_ANNOTATIONS: &[&str] = &[
+    "Inject",
+    "Redirect",
+    "ModifyArg",
+    "ModifyArgs",
+    "ModifyVariable",
+    "ModifyConstant",
+    "ModifyReturnValue",
+    "ModifyExpressionValue",
+    "ModifyReceiver",
+    "WrapOperation",
+    "WrapWithCondition",
+    "WrapMethod",
+];
+
+// 类型名前可能出现的关键字；其他标识符之后的同名单词视为变量名
+const TYPE_PREFIX_KEYWORDS: &[&str] = &[
+    "new",
+    "extends",
+    "implements",
+    "instanceof",
+    "throws",
+    "throw",
+    "return",
+    "case",
+    "else",
+    "final",
+    "static",
+    "public",
+    "private",
+    "protected",
+    "abstract",
+    "synchronized",
+    "volatile",
+    "transient",
+    "default",
+    "sealed",
+    "permits",
+];
+
+// 按 Java 词法切分，返回 (是否为代码, 片段)；注释、字符串、文本块与字符字面量不算代码
+fn split_java_code(src: &str) -> Vec<(bool, &str)> {
+    let bytes = src.as_bytes();
+    let len = bytes.len();
+    let mut out = Vec::new();
+    let (mut start, mut i) = (0, 0);
+    while i < len {
+        let rest = &bytes[i..];
+        let end = if rest.starts_with(b"//") {
+            Some(src[i..].find('\n').map(|n| i + n).unwrap_or(len))
+        } else if rest.starts_with(b"/*") {
+            Some(src[i + 2..].find("*/").map(|n| i + n + 4).unwrap_or(len))
+        } else if rest.starts_with(b"\"\"\"") {
+            Some(
+                src[i + 3..]
+                    .find("\"\"\"")
+                    .map(|n| i + n + 6)
+                    .unwrap_or(len),
+            )
+        } else if bytes[i] == b'"' || bytes[i] == b'\'' {
+            let quote = bytes[i];
+            let mut j = i + 1;
+            while j < len && bytes[j] != quote && bytes[j] != b'\n' {
+                j += if bytes[j] == b'\\' { 2 } else { 1 };
+            }
+            Some(if j < len && bytes[j] == quote {
+                j + 1
+            } else {
+                j.min(len)
+            })
+        } else {
+            None
+        };
+        match end {
+            Some(end) => {
+                if start < i {
+                    out.push((true, &src[start..i]));
+                }
+                out.push((false, &src[i..end]));
+                i = end;
+                start = end;
+            }
+            None => i += 1,
+        }
+    }
+    if start < len {
+        out.push((true, &src[start..]));
+    }
+    out
+}
+
+// 判断代码片段中 start..end 处的简单名是否用作类型，而不是变量、方法或成员访问
+fn is_type_position(code: &str, start: usize, end: usize) -> bool {
+    let line_start = code[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = code[line_start..].trim_start();
+    if line.starts_with("import ") || line.starts_with("package ") {
+        return false;
+    }
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let before = code[..start].trim_end();
+    let after = code[end..].trim_start();
+    if before.ends_with('.') || before.ends_with("::") {
+        return false;
+    }
+    let prev_word = before.rsplit(|c: char| !is_ident(c)).next().unwrap_or("");
+    if after.starts_with('(') {
+        return prev_word == "new";
+    }
+    if after.starts_with('=') && !after.starts_with("==") {
+        return false;
+    }
+    if prev_word.is_empty() || TYPE_PREFIX_KEYWORDS.contains(&prev_word) {
+        return true;
+    }
+    // 注解之后的名称仍是类型，例如 @Shadow Foo foo
+    before[..before.len() - prev_word.len()]
+        .trim_end()
+        .ends_with('@')
+}
+
+// 找到与 open 之前的左括号配对的右括号位置，跳过字符串中的括号
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let (mut depth, mut in_str, mut i) = (1, false, open);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if in_str => i += 1,
+            b'"' => in_str = !in_str,
+            b'(' if !in_str => depth += 1,
+            b')' if !in_str => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// 只在指定注解的参数列表内做替换，f 接收参数文本及其在 text 中的起始位置
+fn replace_annotation_args(
+    text: &str,
+    names: &[&str],
+    mut f: impl FnMut(&str, usize) -> String,
+) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for c in ANNOTATION_START_RE.captures_iter(text) {
+        let whole = c.get(0).unwrap();
+        if whole.start() < last || !names.contains(&&c[1]) {
+            continue;
+        }
+        let open = whole.end();
+        let Some(close) = matching_paren(text, open) else {
+            continue;
+        };
+        out.push_str(&text[last..open]);
+        out.push_str(&f(&text[open..close], open));
+        last = close;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+fn remap_mixin_source(
+    src: &str,
+    maps: &Maps,
+    from: Ns,
+    to: Ns,
+    file: &str,
+    issues: &mut Vec<MixinIssue>,
+) -> (String, usize) {
+    let line_of = |text: &str, offset: usize| text[..offset].matches('\n').count() + 1;
+    let mut changed = 0;
+    let mut issue = |line: usize, kind: &'static str, reference: &str| {
+        issues.push(MixinIssue {
+            file: file.to_string(),
+            line,
+            kind,
+            reference: reference.to_string(),
+        })
+    };
+
+    // import 行：记录简单名到 intermediary 的对应，并收集需要改名的简单名
+    let mut simple_to_inter: HashMap<String, String> = HashMap::new();
+    let mut renames: HashMap<String, String> = HashMap::new();
+    let text = MIXIN_IMPORT_RE.replace_all(src, |c: &regex::Captures| {
+        let dotted = &c[2];
+        let Some(inter) = resolve_dotted_class(maps, from, dotted) else {
+            if dotted.starts_with("net.minecraft.") {
+                issue(line_of(src, c.get(0).unwrap().start()), "class", dotted);
+            }
+            return c[0].to_string();
+        };
+        let out = from_inter_class(maps, to, &inter).replace(['/', '$'], ".");
+        let old_simple = dotted.rsplit('.').next().unwrap_or(dotted);
+        let new_simple = out.rsplit('.').next().unwrap_or(&out).to_string();
+        if old_simple != new_simple {
+            renames.insert(old_simple.to_string(), new_simple.clone());
+        }
+        simple_to_inter.insert(new_simple, inter);
+        if out != dotted {
+            changed += 1;
+        }
+        format!("{}{}{}", &c[1], out, &c[3])
+    });
+    let mut text = text.into_owned();
+    if !renames.is_empty() {
+        let mut keys: Vec<&String> = renames.keys().collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.len()));
+        let alternation = keys
+            .iter()
+            .map(|k| regex::escape(k))
+            .collect::<Vec<_>>()
+            .join("|");
+        let re_simple = Regex::new(&format!(r"\b({})\b", alternation)).unwrap();
+        // 只改代码中处于类型位置的简单名，注释与字符串字面量原样保留
+        text = split_java_code(&text)
+            .into_iter()
+            .map(|(is_code, part)| {
+                if !is_code {
+                    return part.to_string();
+                }
+                re_simple
+                    .replace_all(part, |c: &regex::Captures| {
+                        let m = c.get(1).unwrap();
+                        if is_type_position(part, m.start(), m.end()) {
+                            renames[m.as_str()].clone()
+                        } else {
+                            m.as_str().to_string()
+                        }
+                    })
+                    .into_owned()
+            })
+            .collect();
+    }
+
+    // @Mixin 目标：类字面量与 targets 字符串
+    let mut owners: Vec<String> = Vec::new();
+    let text = MIXIN_ANNOTATION_RE.replace_all(&text, |c: &regex::Captures| {
+        let line = line_of(&text, c.get(0).unwrap().start());
+        let args = CLASS_LITERAL_RE.replace_all(&c[1], |lc: &regex::Captures| {
+            let name = &lc[1];
+            if !name.contains('.') {
+                if let Some(inter) = simple_to_inter.get(name) {
+                    owners.push(inter.clone());
+                }
+                return lc[0].to_string();
+            }
+            match resolve_dotted_class(maps, from, name) {
+                Some(inter) => {
+                    let out = from_inter_class(maps, to, &inter).replace(['/', '$'], ".");
+                    owners.push(inter);
+                    changed += 1;
+                    format!("{}.class", out)
+                }
+                None => {
+                    issue(line, "class", name);
+                    lc[0].to_string()
+                }
+            }
+        });
+        let args = STRING_LITERAL_RE.replace_all(&args, |sc: &regex::Captures| {
+            let raw = &sc[1];
+            let dotted_style = raw.contains('.');
+            let internal = raw.replace('.', "/");
+            let inter = class_inter_of(maps, from, &internal)
+                .or_else(|| resolve_dotted_class(maps, from, raw));
+            match inter {
+                Some(inter) => {
+                    let mut out = from_inter_class(maps, to, &inter);
+                    if dotted_style {
+                        out = out.replace('/', ".");
+                    }
+                    owners.push(inter);
+                    changed += 1;
+                    format!("\"{}\"", out)
+                }
+                None => {
+                    issue(line, "class", raw);
+                    sc[0].to_string()
+                }
+            }
+        });
+        format!("@Mixin({})", args)
+    });
+    let text = text.into_owned();
+
+    // 注入器注解中的 method 选择器，相对于 @Mixin 目标类解析
+    let text = replace_annotation_args(&text, INJECTOR_ANNOTATIONS, |args, offset| {
+        MIXIN_METHOD_RE
+            .replace_all(args, |c: &regex::Captures| {
+                let line = line_of(&text, offset + c.get(0).unwrap().start());
+                let whole = c[0].to_string();
+                let value = STRING_LITERAL_RE.replace_all(&c[1], |sc: &regex::Captures| {
+                    let selector = &sc[1];
+                    let mapped = if selector.starts_with('L') && selector.contains(';') {
+                        remap_member_ref(maps, from, to, selector).ok()
+                    } else {
+                        owners
+                            .iter()
+                            .find_map(|owner| remap_selector(maps, from, to, owner, selector).ok())
+                    };
+                    match mapped {
+                        Some(out) => {
+                            if out != selector {
+                                changed += 1;
+                            }
+                            format!("\"{}\"", out)
+                        }
+                        None => {
+                            issue(line, "method", selector);
+                            sc[0].to_string()
+                        }
+                    }
+                });
+                whole.replacen(&c[1], &value, 1)
+            })
+            .into_owned()
+    });
+
+    // @At(target = "L...;...")
+    let text = replace_annotation_args(&text, &["At"], |args, offset| {
+        MIXIN_TARGET_RE
+            .replace_all(args, |c: &regex::Captures| {
+                let reference = &c[1];
+                if !(reference.starts_with('L') && reference.contains(';')) {
+                    return c[0].to_string();
+                }
+                match remap_member_ref(maps, from, to, reference) {
+                    Ok(out) => {
+                        if out != reference {
+                            changed += 1;
+                        }
+                        c[0].replacen(reference, &out, 1)
+                    }
+                    Err(_) => {
+                        issue(
+                            line_of(&text, offset + c.get(0).unwrap().start()),
+                            "target",
+                            reference,
+                        );
+                        c[0].to_string()
+                    }
+                }
+            })
+            .into_owned()
+    });
+    (text, changed)
+}
+
+fn collect_java_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !PROJECT_SKIP_DIRS.contains(&name.as_str()) {
+                collect_java_files(&path, out);
+            }
+        } else if path.extension().map(|e| e == "java").unwrap_or(false) {
+            out.push(path);
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn remap_mixins(
+    input_path: String,
+    mc_version: Option<String>,
+    input_mapping: String,
+    output_mapping: String,
+    options: Option<ConvertOptions>,
+) -> Result<Value, String> {
+    let options = options.unwrap_or_default();
+    let from = ns_of(&input_mapping).map_err(|e| e.to_string())?;
+    let to = ns_of(&output_mapping).map_err(|e| e.to_string())?;
+    let input = Path::new(&input_path);
+    if !input.exists() {
+        return Err(format!("Input path not found: {}", input_path));
+    }
+    let sets = mapping_sets(&[from, to]);
+    let mc_version = crate::settings::resolve_mc_version(mc_version);
+    let maps = load_maps(options.mapping_files.clone(), mc_version, &sets)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+    if input.is_dir() {
+        collect_java_files(input, &mut files);
+        files.sort();
+    } else {
+        files.push(input.to_path_buf());
+    }
+    // 目录输入时输出路径视为镜像目录；未指定输出时不原地改写，
+    // 目录写到同级的 <name>-remapped，单个文件写到 remapped/ 子目录
+    let base = if input.is_dir() {
+        input
+    } else {
+        input.parent().unwrap_or_else(|| Path::new("."))
+    };
+    let explicit = options
+        .output_path
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty());
+    let output_root = match explicit {
+        Some(p) => base.join(p),
+        None if input.is_dir() => {
+            let name = input
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "mixins".to_string());
+            input.with_file_name(format!("{}-remapped", name))
+        }
+        None => base
+            .join("remapped")
+            .join(input.file_name().unwrap_or_default()),
+    };
+    let mut issues = Vec::new();
+    let mut results = Vec::new();
+    for file in &files {
+        let src = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let rel = file.strip_prefix(base).unwrap_or(file);
+        let display = rel.to_string_lossy().to_string();
+        let (out, changed) = remap_mixin_source(&src, &maps, from, to, &display, &mut issues);
+        let out_path = if input.is_dir() {
+            output_root.join(rel)
+        } else {
+            output_root.clone()
+        };
+        let mut entry = json!({
+            "path": display,
             "outputPath": out_path.to_string_lossy(),
-            "converted": stats.converted,
-            "failed": stats.failed,
-            "failures": stats.failures
-        }));
+            "changed": changed
+        });
+        if options.dry_run {
+            entry["content"] = json!(out);
+        } else if out != src || out_path != *file {
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&out_path, out).map_err(|e| e.to_string())?;
+        }
+        results.push(entry);
+    }
+    crate::util::log_event(
+        "info",
+        &format!(
+            "remap_mixins {} {}->{} files={} unmapped={}",
+            input_path,
+            ns_name(from),
+            ns_name(to),
+            results.len(),
+            issues.len()
+        ),
+    );
+    Ok(json!({
+        "inputMapping": ns_name(from),
+        "outputMapping": ns_name(to),
+        "written": !options.dry_run,
+        "files": results,
+        "unmapped": issues
+    }))
+}
+
+const LOOM_INJECTED_INTERFACES: &str = "loom:injected_interfaces";
+
+fn remap_class_name(
+    maps: &Maps,
+    from: Ns,
+    to: Ns,
+    name: &str,
+    unmapped: &mut Vec<String>,
+) -> String {
+    // 接口可以带泛型参数，例如 com/example/Foo<Lnet/minecraft/class_1799;>
+    let (base, generics) = match name.find('<') {
+        Some(i) => (&name[..i], &name[i..]),
+        None => (name, ""),
+    };
+    let internal = base.replace('.', "/");
+    let base_out = match class_inter_of(maps, from, &internal) {
+        Some(inter) => from_inter_class(maps, to, &inter),
+        None => {
+            if is_game_class(&internal) {
+                unmapped.push(name.to_string());
+            }
+            internal
+        }
+    };
+    format!("{}{}", base_out, translate_desc(maps, from, to, generics))
+}
+
+fn remap_interface_map(
+    maps: &Maps,
+    from: Ns,
+    to: Ns,
+    input: &serde_json::Map<String, Value>,
+    unmapped: &mut Vec<String>,
+) -> serde_json::Map<String, Value> {
+    let mut out = serde_json::Map::new();
+    for (target, interfaces) in input {
+        let target_out = remap_class_name(maps, from, to, target, unmapped);
+        let interfaces_out: Vec<Value> = interfaces
+            .as_array()
+            .map(|list| {
+                list.iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|i| json!(remap_class_name(maps, from, to, i, unmapped)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        out.insert(target_out, json!(interfaces_out));
+    }
+    out
+}
+
+fn merge_interface_maps(
+    into: &mut serde_json::Map<String, Value>,
+    from: serde_json::Map<String, Value>,
+) {
+    for (target, interfaces) in from {
+        let entry = into.entry(target).or_insert_with(|| json!([]));
+        let (Some(list), Some(new)) = (entry.as_array_mut(), interfaces.as_array()) else {
+            continue;
+        };
+        for i in new {
+            if !list.contains(i) {
+                list.push(i.clone());
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn convert_injected_interfaces(
+    input_path: String,
+    mc_version: Option<String>,
+    direction: String,
+    options: Option<ConvertOptions>,
+) -> Result<Value, String> {
+    let options = options.unwrap_or_default();
+    let policy = conflict_policy_of(options.on_conflict.as_deref()).map_err(|e| e.to_string())?;
+    let dir = direction.to_lowercase();
+    // Loom 使用 intermediary 名，NeoForge 的 interfaces.json 使用 Mojang 名
+    let (from, to, default_name) = match dir.as_str() {
+        "fabric_to_neoforge" => (Ns::Intermediary, Ns::Official, "interfaces.json"),
+        "neoforge_to_fabric" => (Ns::Official, Ns::Intermediary, "fabric.mod.json"),
+        _ => return Err("Unsupported direction".to_string()),
+    };
+    let p = Path::new(&input_path);
+    let content =
+        std::fs::read_to_string(p).map_err(|e| format!("Failed to read {}: {}", input_path, e))?;
+    let input: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let source = if from == Ns::Intermediary {
+        input["custom"][LOOM_INJECTED_INTERFACES].as_object()
+    } else {
+        input.as_object()
+    }
+    .ok_or_else(|| format!("No injected interfaces found in {}", input_path))?;
+    let mc_version = crate::settings::resolve_mc_version(mc_version);
+    let maps = load_maps(options.mapping_files.clone(), mc_version, &["mojang"])
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut unmapped = Vec::new();
+    let converted = remap_interface_map(&maps, from, to, source, &mut unmapped);
+    let count = converted.len();
+
+    let parent = p.parent().unwrap_or_else(|| Path::new("."));
+    let mut out_path = match options.output_path.as_deref().map(str::trim) {
+        Some(custom) if !custom.is_empty() => parent.join(custom),
+        _ => parent.join(default_name),
+    };
+    let mut existing = None;
+    if out_path.exists() {
+        match policy {
+            ConflictPolicy::Fail => {
+                return Err(format!(
+                    "Output file already exists: {}",
+                    out_path.to_string_lossy()
+                ))
+            }
+            ConflictPolicy::Suffix => out_path = suffixed_path(&out_path),
+            ConflictPolicy::Overwrite | ConflictPolicy::Merge => {
+                let text = std::fs::read_to_string(&out_path).map_err(|e| e.to_string())?;
+                existing = Some(serde_json::from_str::<Value>(&text).map_err(|e| e.to_string())?);
+            }
+        }
+    }
+    let merge = policy == ConflictPolicy::Merge;
+    let output = if to == Ns::Intermediary {
+        // 写入 fabric.mod.json 时只替换 custom 下的这一项，其余字段保持原样
+        let mut root = existing
+            .filter(|v| v.is_object())
+            .unwrap_or_else(|| json!({}));
+        if !root["custom"].is_object() {
+            root["custom"] = json!({});
+        }
+        let slot = &mut root["custom"][LOOM_INJECTED_INTERFACES];
+        match slot.as_object_mut() {
+            Some(current) if merge => merge_interface_maps(current, converted),
+            _ => *slot = Value::Object(converted),
+        }
+        root
+    } else {
+        match existing.as_ref().and_then(|v| v.as_object()) {
+            Some(current) if merge => {
+                let mut current = current.clone();
+                merge_interface_maps(&mut current, converted);
+                Value::Object(current)
+            }
+            _ => Value::Object(converted),
+        }
+    };
+    let text = serde_json::to_string_pretty(&output).map_err(|e| e.to_string())? + "\n";
+    if !options.dry_run {
+        std::fs::write(&out_path, &text).map_err(|e| e.to_string())?;
+    }
+    crate::util::log_event(
+        "info",
+        &format!(
+            "convert_injected_interfaces {} {} targets={} unmapped={}",
+            input_path,
+            dir,
+            count,
+            unmapped.len()
+        ),
+    );
+    let mut result = json!({
+        "outputPath": out_path.to_string_lossy(),
+        "written": !options.dry_run,
+        "converted": count,
+        "unmapped": unmapped
+    });
+    if options.dry_run {
+        result["content"] = json!(text);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> AtModifier {
+        parse_at_modifier(s).unwrap()
+    }
+
+    #[test]
+    fn aw_to_at_modifiers() {
+        let cases = [
+            (AwAccess::Accessible, EntryKind::Class, Some("public")),
+            (AwAccess::Extendable, EntryKind::Class, Some("public-f")),
+            (AwAccess::Extendable, EntryKind::Method, Some("protected-f")),
+            (AwAccess::Mutable, EntryKind::Field, Some("public-f")),
+            (AwAccess::Mutable, EntryKind::Method, None),
+            (AwAccess::Extendable, EntryKind::Field, None),
+        ];
+        for (access, kind, expected) in cases {
+            let got = aw_to_at_modifier(access, kind).map(at_modifier_str);
+            assert_eq!(got.as_deref(), expected, "{:?} {:?}", access, kind);
+        }
+        assert_eq!(
+            parse_aw_access("transitive-mutable"),
+            Some((AwAccess::Mutable, true))
+        );
+        assert_eq!(
+            parse_at_modifier("protected+f")
+                .map(at_modifier_str)
+                .as_deref(),
+            Some("protected+f")
+        );
+        assert!(parse_at_modifier("package").is_none());
+    }
+
+    #[test]
+    fn at_to_aw_modifiers() {
+        let (accesses, note) = at_to_aw_accesses(at("public-f"), EntryKind::Field).unwrap();
+        assert_eq!(accesses, vec![AwAccess::Accessible, AwAccess::Mutable]);
+        assert!(note.is_none());
+        let (accesses, note) = at_to_aw_accesses(at("public-f"), EntryKind::Method).unwrap();
+        assert_eq!(accesses, vec![AwAccess::Accessible, AwAccess::Extendable]);
+        assert!(note.is_none());
+        // extendable method 本身就是 protected，不算放宽
+        let (accesses, note) = at_to_aw_accesses(at("protected-f"), EntryKind::Method).unwrap();
+        assert_eq!(accesses, vec![AwAccess::Extendable]);
+        assert!(note.is_none());
+        for kind in [EntryKind::Class, EntryKind::Method, EntryKind::Field] {
+            let (accesses, note) = at_to_aw_accesses(at("protected"), kind).unwrap();
+            assert_eq!(accesses, vec![AwAccess::Accessible]);
+            assert!(note.unwrap().contains("widened to public"));
+        }
+        assert!(at_to_aw_accesses(at("private"), EntryKind::Field).is_err());
+        assert!(at_to_aw_accesses(at("public+f"), EntryKind::Field).is_err());
+    }
+
+    #[test]
+    fn transitive_and_mutable_are_approximated() {
+        let tiny = "v1\tofficial\tintermediary\tnamed\n\
+                    CLASS\ta\tnet/minecraft/class_1\tnet/minecraft/Foo\n\
+                    FIELD\ta\tI\td\tfield_7\tcount\n";
+        let tree = crate::mappings::parse(tiny, crate::mappings::MappingFormat::TinyV1).unwrap();
+        let mut maps = Maps::default();
+        load_tree(&mut maps, &tree, Ns::Yarn).unwrap();
+        let lines = vec!["transitive-mutable field net/minecraft/Foo count I".to_string()];
+        let (out, mut stats) = aw_to_at_lines(&lines, &maps, Ns::Yarn, Ns::Intermediary);
+        stats.close_line(&out);
+        assert!(out.contains(&"public-f net.minecraft.class_1 field_7".to_string()));
+        assert_eq!(stats.approximated.len(), 2);
+        assert_eq!(stats.report[0].status, "approximated");
+    }
+
+    #[test]
+    fn parchment_loads_on_mojang_names() {
+        let dir = std::env::temp_dir().join(format!("awat-parchment-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tiny = "v1\tofficial\tintermediary\n\
+                    CLASS\ta\tnet/minecraft/class_1\n\
+                    CLASS\tb\tnet/minecraft/class_2\n\
+                    METHOD\ta\t(Lb;)V\tc\tmethod_5\n\
+                    FIELD\ta\tI\td\tfield_7\n";
+        let proguard = "net.minecraft.Foo -> a:\n    \
+                        int count -> d\n    \
+                        void copy(net.minecraft.Bar) -> c\n\
+                        net.minecraft.Bar -> b:\n";
+        let parchment = r#"{"classes":[{"name":"net/minecraft/Foo",
+            "fields":[{"name":"count","descriptor":"I"}],
+            "methods":[{"name":"copy","descriptor":"(Lnet/minecraft/Bar;)V"},
+                       {"name":"<init>","descriptor":"()V"},
+                       {"name":"gone","descriptor":"()V"}]}]}"#;
+        for (name, text) in [("i.tiny", tiny), ("c.txt", proguard), ("p.json", parchment)] {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        let input = |name: &str| MappingFileInput {
+            path: dir.join(name).to_string_lossy().to_string(),
+            format: None,
+            named_namespace: None,
+        };
+        // 输入顺序不影响加载顺序
+        let maps = load_local_maps(&[input("p.json"), input("c.txt"), input("i.tiny")]).unwrap();
+        let tree = crate::mappings::load_file(&dir.join("p.json"), None).unwrap();
+        assert_eq!(
+            unmatched_entries(&maps, &tree, Ns::Official),
+            vec!["net/minecraft/Foo.gone()V".to_string()]
+        );
+        assert!(load_local_maps(&[input("p.json"), input("i.tiny")]).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn lines(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_widens_and_keeps_comments() {
+        let existing = lines(&[
+            "# my header",
+            "",
+            "protected net.minecraft.B foo",
+            "# keep me",
+            "public net.minecraft.A",
+            "protected-f net.minecraft.A bar()V # why",
+        ]);
+        let new = disclaimer_lines()
+            .into_iter()
+            .chain(lines(&[
+                "public net.minecraft.B foo",
+                "public net.minecraft.A",
+                "public net.minecraft.A baz",
+                "protected net.minecraft.A bar()V",
+            ]))
+            .collect();
+        assert_eq!(
+            merge_into_existing(existing, new, false),
+            [
+                "# my header",
+                "",
+                "# keep me",
+                "public net.minecraft.A",
+                "public net.minecraft.A baz",
+                "protected-f net.minecraft.A bar()V # why",
+                "public net.minecraft.B foo",
+            ]
+        );
+        // 重复条目前的注释都保留，同一注释只保留一次
+        let existing = lines(&[
+            "public net.minecraft.A",
+            "# first",
+            "public net.minecraft.A",
+        ]);
+        let new = lines(&[
+            "# second",
+            "public net.minecraft.A",
+            "# first",
+            "public net.minecraft.A",
+        ]);
+        assert_eq!(
+            merge_into_existing(existing, new, false),
+            ["# first", "# second", "public net.minecraft.A"]
+        );
+    }
+
+    #[test]
+    fn merge_aw_keeps_header_and_transitive() {
+        let existing = lines(&[
+            "accessWidener v2 named",
+            "accessible class a/B",
+            "accessible field a/A x I",
+        ]);
+        let new = lines(&[
+            "accessWidener v2 named",
+            "transitive-accessible class a/B",
+            "mutable field a/A x I",
+            "accessible field a/A x I",
+        ]);
+        assert_eq!(
+            merge_into_existing(existing, new, true),
+            [
+                "accessWidener v2 named",
+                "accessible field a/A x I",
+                "mutable field a/A x I",
+                "transitive-accessible class a/B",
+            ]
+        );
+    }
+
+    #[test]
+    fn conflict_policies() {
+        assert_eq!(conflict_policy_of(None).unwrap(), ConflictPolicy::Overwrite);
+        assert_eq!(
+            conflict_policy_of(Some("Merge")).unwrap(),
+            ConflictPolicy::Merge
+        );
+        assert!(conflict_policy_of(Some("replace")).is_err());
+
+        let dir = std::env::temp_dir().join(format!("awat-conflict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mod.cfg");
+        let _ = std::fs::remove_file(&path);
+        let new = lines(&["public net.minecraft.A"]);
+        // 文件不存在时所有策略都直接写入
+        let (out, content) =
+            resolve_output(ConflictPolicy::Fail, &path, new.clone(), false).unwrap();
+        assert_eq!((out, content), (path.clone(), new.clone()));
+
+        write_lines(&path, &lines(&["public net.minecraft.B"])).unwrap();
+        assert!(resolve_output(ConflictPolicy::Fail, &path, new.clone(), false).is_err());
+        let (out, content) =
+            resolve_output(ConflictPolicy::Overwrite, &path, new.clone(), false).unwrap();
+        assert_eq!((out, content), (path.clone(), new.clone()));
+        let (out, _) = resolve_output(ConflictPolicy::Suffix, &path, new.clone(), false).unwrap();
+        assert_eq!(out, dir.join("mod-1.cfg"));
+        let (out, content) = resolve_output(ConflictPolicy::Merge, &path, new, false).unwrap();
+        assert_eq!(out, path);
+        assert_eq!(
+            content,
+            ["public net.minecraft.A", "public net.minecraft.B"]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn mixin_remap_touches_types_and_injector_args_only() {
+        let tiny = "v1\tofficial\tintermediary\n\
+                    CLASS\ta\tnet/minecraft/class_1\n\
+                    CLASS\tb\tnet/minecraft/class_2\n\
+                    METHOD\ta\t(Lb;)V\tc\tmethod_5\n\
+                    FIELD\ta\tI\td\tfield_7\n";
+        let proguard = "net.minecraft.Foo -> a:\n    \
+                        int count -> d\n    \
+                        void copy(net.minecraft.Bar) -> c\n\
+                        net.minecraft.Bar -> b:\n";
+        let mut maps = Maps::default();
+        for (text, format) in [
+            (tiny, crate::mappings::MappingFormat::TinyV1),
+            (proguard, crate::mappings::MappingFormat::ProGuard),
+        ] {
+            let tree = crate::mappings::parse(text, format).unwrap();
+            load_tree(&mut maps, &tree, Ns::Yarn).unwrap();
+        }
+        let src = "import net.minecraft.class_1;\n\
+                   @Mixin(class_1.class)\n\
+                   class M {\n\
+                   \x20   // class_1 is kept in comments\n\
+                   \x20   @Shadow class_1 self;\n\
+                   \x20   @Inject(method = \"method_5\", at = @At(value = \"FIELD\", target = \"Lnet/minecraft/class_1;field_7:I\"))\n\
+                   \x20   void a(class_1 other) { int class_1 = 0; other.class_1(); }\n\
+                   \x20   String method = \"method_5\";\n\
+                   }\n";
+        let mut issues = Vec::new();
+        let (out, _) = remap_mixin_source(
+            src,
+            &maps,
+            Ns::Intermediary,
+            Ns::Official,
+            "M.java",
+            &mut issues,
+        );
+        assert!(out.contains("import net.minecraft.Foo;"));
+        assert!(out.contains("@Mixin(Foo.class)"));
+        assert!(out.contains("// class_1 is kept in comments"));
+        assert!(out.contains("@Shadow Foo self;"));
+        assert!(out.contains("@Inject(method = \"copy\""));
+        assert!(out.contains("target = \"Lnet/minecraft/Foo;count:I\""));
+        assert!(out.contains("void a(Foo other) { int class_1 = 0; other.class_1(); }"));
+        assert!(out.contains("String method = \"method_5\";"));
+        assert!(issues.is_empty(), "{:?}", issues);
     }
-    Err("Unsupported direction".to_string())
 }
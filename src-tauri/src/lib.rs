@@ -2,9 +2,12 @@ mod cache;
 mod cf;
 mod convert;
 mod gradle;
+mod logging;
+mod mappings;
 mod mojang;
 mod mr;
 mod operations;
+mod settings;
 mod util;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -13,6 +16,8 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .setup(|_app| {
+            crate::cache::purge_incompatible_entries();
+            crate::settings::apply_on_startup();
             tauri::async_runtime::spawn(crate::mojang::refresh_manifest_cache_on_startup());
             Ok(())
         })
@@ -27,8 +32,23 @@ pub fn run() {
             operations::get_batch_mod_briefs,
             operations::save_log,
             operations::clear_all_caches,
+            operations::get_cache_stats,
+            operations::invalidate_cache_category,
+            operations::invalidate_project_cache,
+            operations::set_cache_max_size,
             operations::refresh_mojang_cache,
-            convert::convert_aw_at
+            operations::list_mc_versions,
+            operations::get_mc_version_info,
+            operations::set_offline_mode,
+            operations::get_offline_mode,
+            operations::get_settings,
+            operations::set_settings,
+            operations::set_cf_api_key,
+            convert::convert_aw_at,
+            convert::validate_aw_at,
+            convert::convert_project_aw_at,
+            convert::remap_mixins,
+            convert::convert_injected_interfaces
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
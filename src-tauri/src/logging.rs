@@ -0,0 +1,145 @@
+use chrono::{Local, SecondsFormat};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::json;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+use crate::util::app_data_dir;
+
+const LOG_FILE: &str = "runtime.log";
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+// 轮转后保留 runtime.log.1 .. runtime.log.3
+const KEEP_ROTATED: usize = 3;
+pub const LEVELS: [&str; 4] = ["debug", "info", "warn", "error"];
+pub const FORMATS: [&str; 2] = ["text", "json"];
+
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(1);
+static JSON_LINES: AtomicBool = AtomicBool::new(false);
+static NEXT_OPERATION: AtomicU64 = AtomicU64::new(1);
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+static API_KEY_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?i)(x-api-key["']?\s*[:=]\s*["']?)[^"'\s,;&}]+"#).unwrap());
+// CurseForge API Key 形如 $2a$10$...
+static CF_KEY_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\$2[aby]\$\d{2}\$[./A-Za-z0-9]{53,}").unwrap());
+static HOME_PATHS: Lazy<Vec<String>> = Lazy::new(|| {
+    let Some(home) = dirs::home_dir().map(|h| h.to_string_lossy().to_string()) else {
+        return Vec::new();
+    };
+    let mut paths = vec![home.clone(), home.replace('\\', "/")];
+    paths.retain(|p| p.len() > 1);
+    paths.dedup();
+    paths
+});
+
+tokio::task_local! {
+    static OPERATION_ID: String;
+}
+
+fn level_rank(level: &str) -> Option<u8> {
+    LEVELS
+        .iter()
+        .position(|l| l.eq_ignore_ascii_case(level))
+        .map(|i| i as u8)
+}
+
+pub fn configure(level: &str, format: &str) {
+    MIN_LEVEL.store(level_rank(level).unwrap_or(1), Ordering::Relaxed);
+    JSON_LINES.store(format.eq_ignore_ascii_case("json"), Ordering::Relaxed);
+}
+
+pub fn log_dir() -> PathBuf {
+    app_data_dir().join("logs")
+}
+
+pub fn redact(s: &str) -> String {
+    let s = API_KEY_RE.replace_all(s, "${1}***");
+    let mut s = CF_KEY_RE.replace_all(&s, "***").into_owned();
+    for home in HOME_PATHS.iter() {
+        s = s.replace(home.as_str(), "~");
+    }
+    s
+}
+
+pub fn current_operation() -> Option<String> {
+    OPERATION_ID.try_with(|id| id.clone()).ok()
+}
+
+// 为一次命令调用分配关联 ID，嵌套调用沿用外层的 ID
+pub async fn with_operation<F: Future>(name: &str, fut: F) -> F::Output {
+    if current_operation().is_some() {
+        return fut.await;
+    }
+    let id = format!(
+        "{}-{}",
+        Local::now().format("%H%M%S"),
+        NEXT_OPERATION.fetch_add(1, Ordering::Relaxed)
+    );
+    OPERATION_ID
+        .scope(id, async move {
+            log_event("debug", &format!("operation_start {}", name));
+            let out = fut.await;
+            log_event("debug", &format!("operation_end {}", name));
+            out
+        })
+        .await
+}
+
+fn rotate_if_needed(path: &Path, incoming: usize) {
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size == 0 || size + incoming as u64 <= MAX_LOG_BYTES {
+        return;
+    }
+    let rotated = |i: usize| path.with_file_name(format!("{}.{}", LOG_FILE, i));
+    let _ = std::fs::remove_file(rotated(KEEP_ROTATED));
+    for i in (1..KEEP_ROTATED).rev() {
+        let _ = std::fs::rename(rotated(i), rotated(i + 1));
+    }
+    let _ = std::fs::rename(path, rotated(1));
+}
+
+pub fn log_event(level: &str, msg: &str) {
+    if level_rank(level).unwrap_or(1) < MIN_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    let ts = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+    let msg = redact(msg);
+    let operation = current_operation();
+    let line = if JSON_LINES.load(Ordering::Relaxed) {
+        json!({ "ts": ts, "level": level, "op": operation, "msg": msg }).to_string()
+    } else {
+        match &operation {
+            Some(op) => format!("[{}][{}][{}] {}", ts, level, op, msg),
+            None => format!("[{}][{}] {}", ts, level, msg),
+        }
+    };
+    {
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = log_dir();
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join(LOG_FILE);
+        rotate_if_needed(&path, line.len() + 1);
+        let _ = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, format!("{}\n", line).as_bytes()));
+    }
+    println!("{}", line);
+}
+
+// 按时间顺序拼接轮转文件与当前日志
+pub fn read_backend_log() -> String {
+    let dir = log_dir();
+    let mut out = String::new();
+    for i in (1..=KEEP_ROTATED).rev() {
+        if let Ok(text) = std::fs::read_to_string(dir.join(format!("{}.{}", LOG_FILE, i))) {
+            out.push_str(&text);
+        }
+    }
+    out.push_str(&std::fs::read_to_string(dir.join(LOG_FILE)).unwrap_or_default());
+    out
+}
@@ -0,0 +1,531 @@
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone, Debug, Default)]
+pub struct MappingTree {
+    pub namespaces: Vec<String>,
+    pub classes: Vec<ClassEntry>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ClassEntry {
+    pub names: Vec<String>,
+    pub fields: Vec<MemberEntry>,
+    pub methods: Vec<MemberEntry>,
+}
+
+// desc 使用第一个命名空间的类名，未知时为空
+#[derive(Clone, Debug, Default)]
+pub struct MemberEntry {
+    pub names: Vec<String>,
+    pub desc: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MappingFormat {
+    TinyV1,
+    TinyV2,
+    ProGuard,
+    Tsrg,
+    Tsrg2,
+    // Parchment 只有 Mojang 名，需与 ProGuard 映射一同加载，用于补全与校验
+    Parchment,
+}
+
+pub fn format_of(s: &str) -> anyhow::Result<MappingFormat> {
+    match s.to_lowercase().as_str() {
+        "tiny" | "tinyv1" | "tiny1" => Ok(MappingFormat::TinyV1),
+        "tinyv2" | "tiny2" => Ok(MappingFormat::TinyV2),
+        "proguard" | "mojang" | "mojmap" => Ok(MappingFormat::ProGuard),
+        "tsrg" | "tsrg1" => Ok(MappingFormat::Tsrg),
+        "tsrg2" => Ok(MappingFormat::Tsrg2),
+        "parchment" => Ok(MappingFormat::Parchment),
+        other => Err(anyhow!("Unknown mapping format: {}", other)),
+    }
+}
+
+pub fn detect_format(content: &str) -> anyhow::Result<MappingFormat> {
+    let first = content
+        .lines()
+        .map(|l| l.trim_end())
+        .find(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .unwrap_or("");
+    if first.starts_with("v1\t") {
+        Ok(MappingFormat::TinyV1)
+    } else if first.starts_with("tiny\t2\t") {
+        Ok(MappingFormat::TinyV2)
+    } else if first.starts_with("tsrg2 ") {
+        Ok(MappingFormat::Tsrg2)
+    } else if first.trim_start().starts_with('{') {
+        Ok(MappingFormat::Parchment)
+    } else if first.contains(" -> ") && first.ends_with(':') {
+        Ok(MappingFormat::ProGuard)
+    } else if first.split_whitespace().count() == 2 {
+        Ok(MappingFormat::Tsrg)
+    } else {
+        Err(anyhow!("Could not detect mapping format"))
+    }
+}
+
+pub fn load_file(path: &Path, format: Option<&str>) -> anyhow::Result<MappingTree> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read mapping file {}", path.to_string_lossy()))?;
+    let format = match format {
+        Some(f) => format_of(f)?,
+        None => detect_format(&content)?,
+    };
+    parse(&content, format)
+        .with_context(|| format!("Failed to parse mapping file {}", path.to_string_lossy()))
+}
+
+pub fn parse(content: &str, format: MappingFormat) -> anyhow::Result<MappingTree> {
+    match format {
+        MappingFormat::TinyV1 => parse_tiny_v1(content),
+        MappingFormat::TinyV2 => parse_tiny_v2(content),
+        MappingFormat::ProGuard => parse_proguard(content),
+        MappingFormat::Tsrg => parse_tsrg(content),
+        MappingFormat::Tsrg2 => parse_tsrg2(content),
+        MappingFormat::Parchment => parse_parchment(content),
+    }
+}
+
+fn class_index(tree: &mut MappingTree, index: &mut HashMap<String, usize>, name0: &str) -> usize {
+    if let Some(i) = index.get(name0) {
+        return *i;
+    }
+    let mut names = vec![String::new(); tree.namespaces.len()];
+    names[0] = name0.to_string();
+    tree.classes.push(ClassEntry {
+        names,
+        ..Default::default()
+    });
+    index.insert(name0.to_string(), tree.classes.len() - 1);
+    tree.classes.len() - 1
+}
+
+fn parse_tiny_v1(content: &str) -> anyhow::Result<MappingTree> {
+    let mut lines = content.lines();
+    let header = lines.next().unwrap_or("");
+    let mut tree = MappingTree {
+        namespaces: header.split('\t').skip(1).map(|s| s.to_string()).collect(),
+        classes: Vec::new(),
+    };
+    let ns_count = tree.namespaces.len();
+    if ns_count == 0 {
+        return Err(anyhow!("Tiny v1 header has no namespaces"));
+    }
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for line in lines {
+        let parts: Vec<&str> = line.split('\t').collect();
+        match parts.first().copied() {
+            Some("CLASS") if parts.len() > ns_count => {
+                let i = class_index(&mut tree, &mut index, parts[1]);
+                tree.classes[i].names = parts[1..=ns_count].iter().map(|s| s.to_string()).collect();
+            }
+            Some(kind @ ("FIELD" | "METHOD")) if parts.len() > ns_count + 2 => {
+                let i = class_index(&mut tree, &mut index, parts[1]);
+                let member = MemberEntry {
+                    names: parts[3..3 + ns_count]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                    desc: parts[2].to_string(),
+                };
+                if kind == "FIELD" {
+                    tree.classes[i].fields.push(member);
+                } else {
+                    tree.classes[i].methods.push(member);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(tree)
+}
+
+fn parse_tiny_v2(content: &str) -> anyhow::Result<MappingTree> {
+    let mut lines = content.lines();
+    let header = lines.next().unwrap_or("");
+    let mut tree = MappingTree {
+        namespaces: header.split('\t').skip(3).map(|s| s.to_string()).collect(),
+        classes: Vec::new(),
+    };
+    let ns_count = tree.namespaces.len();
+    if ns_count == 0 {
+        return Err(anyhow!("Tiny v2 header has no namespaces"));
+    }
+    for line in lines {
+        if let Some(rest) = line.strip_prefix("c\t") {
+            let names: Vec<String> = rest.split('\t').map(|s| s.to_string()).collect();
+            if names.len() >= ns_count {
+                tree.classes.push(ClassEntry {
+                    names: names[..ns_count].to_vec(),
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+        // 更深层的缩进是参数、局部变量和注释
+        let (kind, rest) = match line.strip_prefix('\t') {
+            Some(r) if !r.starts_with('\t') => match r.split_once('\t') {
+                Some((k, rest)) => (k, rest),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let class = match tree.classes.last_mut() {
+            Some(c) => c,
+            None => continue,
+        };
+        let parts: Vec<&str> = rest.split('\t').collect();
+        if parts.len() < ns_count + 1 {
+            continue;
+        }
+        let member = MemberEntry {
+            names: parts[1..=ns_count].iter().map(|s| s.to_string()).collect(),
+            desc: parts[0].to_string(),
+        };
+        match kind {
+            "f" => class.fields.push(member),
+            "m" => class.methods.push(member),
+            _ => {}
+        }
+    }
+    Ok(tree)
+}
+
+fn java_type_to_desc(ty: &str) -> String {
+    let mut base = ty.trim();
+    let mut dims = 0;
+    while let Some(b) = base.strip_suffix("[]") {
+        base = b;
+        dims += 1;
+    }
+    let prim = match base {
+        "void" => Some("V"),
+        "boolean" => Some("Z"),
+        "byte" => Some("B"),
+        "char" => Some("C"),
+        "short" => Some("S"),
+        "int" => Some("I"),
+        "long" => Some("J"),
+        "float" => Some("F"),
+        "double" => Some("D"),
+        _ => None,
+    };
+    let mut out = "[".repeat(dims);
+    match prim {
+        Some(p) => out.push_str(p),
+        None => {
+            out.push('L');
+            out.push_str(&base.replace('.', "/"));
+            out.push(';');
+        }
+    }
+    out
+}
+
+fn parse_proguard(content: &str) -> anyhow::Result<MappingTree> {
+    let mut tree = MappingTree {
+        namespaces: vec!["mojang".to_string(), "obf".to_string()],
+        classes: Vec::new(),
+    };
+    for line in content.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            let (named, obf) = match line.trim_end_matches(':').split_once(" -> ") {
+                Some(p) => p,
+                None => continue,
+            };
+            tree.classes.push(ClassEntry {
+                names: vec![named.replace('.', "/"), obf.replace('.', "/")],
+                ..Default::default()
+            });
+            continue;
+        }
+        let class = match tree.classes.last_mut() {
+            Some(c) => c,
+            None => continue,
+        };
+        let (left, obf) = match line.trim().split_once(" -> ") {
+            Some(p) => p,
+            None => continue,
+        };
+        // 方法行形如 `1:3:void foo(int):10:12`，去掉行号
+        let left = left.trim_start_matches(|c: char| c.is_ascii_digit() || c == ':');
+        let (ty, name_part) = match left.split_once(' ') {
+            Some(p) => p,
+            None => continue,
+        };
+        if let Some(paren) = name_part.find('(') {
+            let name = &name_part[..paren];
+            let close = name_part.find(')').unwrap_or(name_part.len());
+            let args = &name_part[paren + 1..close];
+            let mut desc = String::from("(");
+            for arg in args.split(',').filter(|a| !a.trim().is_empty()) {
+                desc.push_str(&java_type_to_desc(arg));
+            }
+            desc.push(')');
+            desc.push_str(&java_type_to_desc(ty));
+            class.methods.push(MemberEntry {
+                names: vec![name.to_string(), obf.to_string()],
+                desc,
+            });
+        } else {
+            class.fields.push(MemberEntry {
+                names: vec![name_part.to_string(), obf.to_string()],
+                desc: java_type_to_desc(ty),
+            });
+        }
+    }
+    Ok(tree)
+}
+
+fn parse_tsrg(content: &str) -> anyhow::Result<MappingTree> {
+    let mut tree = MappingTree {
+        namespaces: vec!["obf".to_string(), "srg".to_string()],
+        classes: Vec::new(),
+    };
+    for line in content.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if !line.starts_with('\t') && !line.starts_with(' ') {
+            if parts.len() == 2 && !parts[0].ends_with('/') {
+                tree.classes.push(ClassEntry {
+                    names: vec![parts[0].to_string(), parts[1].to_string()],
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+        let class = match tree.classes.last_mut() {
+            Some(c) => c,
+            None => continue,
+        };
+        match parts.len() {
+            2 => class.fields.push(MemberEntry {
+                names: vec![parts[0].to_string(), parts[1].to_string()],
+                desc: String::new(),
+            }),
+            3 => class.methods.push(MemberEntry {
+                names: vec![parts[0].to_string(), parts[2].to_string()],
+                desc: parts[1].to_string(),
+            }),
+            _ => {}
+        }
+    }
+    Ok(tree)
+}
+
+fn parse_tsrg2(content: &str) -> anyhow::Result<MappingTree> {
+    let mut lines = content.lines();
+    let header = lines.next().unwrap_or("");
+    let mut tree = MappingTree {
+        namespaces: header
+            .split_whitespace()
+            .skip(1)
+            .map(|s| s.to_string())
+            .collect(),
+        classes: Vec::new(),
+    };
+    let ns_count = tree.namespaces.len();
+    if ns_count == 0 {
+        return Err(anyhow!("TSRG2 header has no namespaces"));
+    }
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with("\t\t") {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if !line.starts_with('\t') {
+            if parts.len() >= ns_count {
+                tree.classes.push(ClassEntry {
+                    names: parts[..ns_count].iter().map(|s| s.to_string()).collect(),
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+        let class = match tree.classes.last_mut() {
+            Some(c) => c,
+            None => continue,
+        };
+        if parts.len() == ns_count + 1 && parts[1].starts_with('(') {
+            class.methods.push(MemberEntry {
+                names: std::iter::once(parts[0])
+                    .chain(parts[2..].iter().copied())
+                    .map(|s| s.to_string())
+                    .collect(),
+                desc: parts[1].to_string(),
+            });
+        } else if parts.len() == ns_count + 1 {
+            class.fields.push(MemberEntry {
+                names: std::iter::once(parts[0])
+                    .chain(parts[2..].iter().copied())
+                    .map(|s| s.to_string())
+                    .collect(),
+                desc: parts[1].to_string(),
+            });
+        } else if parts.len() == ns_count {
+            class.fields.push(MemberEntry {
+                names: parts.iter().map(|s| s.to_string()).collect(),
+                desc: String::new(),
+            });
+        }
+    }
+    Ok(tree)
+}
+
+#[derive(Deserialize)]
+struct ParchmentFile {
+    #[serde(default)]
+    classes: Vec<ParchmentClass>,
+}
+
+#[derive(Deserialize)]
+struct ParchmentClass {
+    name: String,
+    #[serde(default)]
+    fields: Vec<ParchmentMember>,
+    #[serde(default)]
+    methods: Vec<ParchmentMember>,
+}
+
+#[derive(Deserialize)]
+struct ParchmentMember {
+    name: String,
+    descriptor: String,
+}
+
+fn parse_parchment(content: &str) -> anyhow::Result<MappingTree> {
+    let file: ParchmentFile = serde_json::from_str(content)?;
+    let member = |m: ParchmentMember| MemberEntry {
+        names: vec![m.name],
+        desc: m.descriptor,
+    };
+    Ok(MappingTree {
+        namespaces: vec!["mojang".to_string()],
+        classes: file
+            .classes
+            .into_iter()
+            .map(|c| ClassEntry {
+                names: vec![c.name],
+                fields: c.fields.into_iter().map(member).collect(),
+                methods: c.methods.into_iter().map(member).collect(),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(v: &[String]) -> Vec<&str> {
+        v.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn tiny_v1_and_v2() {
+        let v1 = "v1\tofficial\tintermediary\tnamed\n\
+                  CLASS\ta\tnet/minecraft/class_1\tnet/minecraft/Foo\n\
+                  METHOD\ta\t(La;)V\tb\tmethod_2\tcopy\n\
+                  FIELD\ta\tI\tc\tfield_3\tcount\n";
+        let tree = parse(v1, detect_format(v1).unwrap()).unwrap();
+        assert_eq!(tree.namespaces, ["official", "intermediary", "named"]);
+        let class = &tree.classes[0];
+        assert_eq!(
+            names(&class.names),
+            ["a", "net/minecraft/class_1", "net/minecraft/Foo"]
+        );
+        assert_eq!(names(&class.methods[0].names), ["b", "method_2", "copy"]);
+        assert_eq!(class.methods[0].desc, "(La;)V");
+        assert_eq!(names(&class.fields[0].names), ["c", "field_3", "count"]);
+
+        let v2 = "tiny\t2\t0\tofficial\tnamed\n\
+                  c\ta\tnet/minecraft/Foo\n\
+                  \tm\t(I)V\tb\ttick\n\
+                  \t\tp\t1\t\tdelta\n\
+                  \tf\tI\tc\tcount\n";
+        let tree = parse(v2, detect_format(v2).unwrap()).unwrap();
+        assert_eq!(tree.namespaces, ["official", "named"]);
+        let class = &tree.classes[0];
+        assert_eq!(names(&class.methods[0].names), ["b", "tick"]);
+        assert_eq!(class.methods.len(), 1);
+        assert_eq!(names(&class.fields[0].names), ["c", "count"]);
+    }
+
+    #[test]
+    fn proguard() {
+        let pg = "# comment\n\
+                  net.minecraft.Foo -> a:\n    \
+                  int count -> c\n    \
+                  1:3:void copy(net.minecraft.Foo,int[]):10:12 -> b\n    \
+                  java.lang.String name() -> d\n";
+        let tree = parse(pg, detect_format(pg).unwrap()).unwrap();
+        assert_eq!(tree.namespaces, ["mojang", "obf"]);
+        let class = &tree.classes[0];
+        assert_eq!(names(&class.names), ["net/minecraft/Foo", "a"]);
+        assert_eq!(names(&class.fields[0].names), ["count", "c"]);
+        assert_eq!(class.fields[0].desc, "I");
+        assert_eq!(names(&class.methods[0].names), ["copy", "b"]);
+        assert_eq!(class.methods[0].desc, "(Lnet/minecraft/Foo;[I)V");
+        assert_eq!(class.methods[1].desc, "()Ljava/lang/String;");
+    }
+
+    #[test]
+    fn tsrg_and_tsrg2() {
+        let tsrg = "a net/minecraft/Foo\n\tc f_1_\n\tb (I)V m_2_\n";
+        let tree = parse(tsrg, detect_format(tsrg).unwrap()).unwrap();
+        assert_eq!(tree.namespaces, ["obf", "srg"]);
+        let class = &tree.classes[0];
+        assert_eq!(names(&class.fields[0].names), ["c", "f_1_"]);
+        assert_eq!(names(&class.methods[0].names), ["b", "m_2_"]);
+        assert_eq!(class.methods[0].desc, "(I)V");
+
+        let tsrg2 = "tsrg2 obf srg id\n\
+                     a net/minecraft/Foo 1\n\
+                     \tb (I)V m_2_ 2\n\
+                     \t\tstatic\n\
+                     \tc f_1_ 3\n";
+        let tree = parse(tsrg2, detect_format(tsrg2).unwrap()).unwrap();
+        assert_eq!(tree.namespaces, ["obf", "srg", "id"]);
+        let class = &tree.classes[0];
+        assert_eq!(names(&class.methods[0].names), ["b", "m_2_", "2"]);
+        assert_eq!(names(&class.fields[0].names), ["c", "f_1_", "3"]);
+    }
+
+    #[test]
+    fn parchment() {
+        let json = r#"{"version":"1.1.0","classes":[{"name":"net/minecraft/Foo",
+            "fields":[{"name":"count","descriptor":"I"}],
+            "methods":[{"name":"tick","descriptor":"(I)V","parameters":[]}]}]}"#;
+        let tree = parse(json, detect_format(json).unwrap()).unwrap();
+        assert_eq!(tree.namespaces, ["mojang"]);
+        let class = &tree.classes[0];
+        assert_eq!(names(&class.names), ["net/minecraft/Foo"]);
+        assert_eq!(names(&class.methods[0].names), ["tick"]);
+        assert_eq!(class.fields[0].desc, "I");
+        assert!(parse("{}", MappingFormat::Parchment)
+            .unwrap()
+            .classes
+            .is_empty());
+    }
+
+    #[test]
+    fn formats_by_name() {
+        assert_eq!(format_of("Mojmap").unwrap(), MappingFormat::ProGuard);
+        assert_eq!(format_of("tiny2").unwrap(), MappingFormat::TinyV2);
+        assert!(format_of("srg").is_err());
+        assert!(detect_format("not a mapping file at all").is_err());
+    }
+}
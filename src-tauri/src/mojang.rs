@@ -1,24 +1,77 @@
+use crate::cache::{is_not_modified, now_millis, read_bincode, write_bincode, Validators};
+use crate::util::{log_event, shorten};
 use anyhow::Context;
-use bincode::{deserialize, serialize};
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::path::PathBuf;
-use std::time::SystemTime;
-
-use crate::cache::cache_path;
-use crate::util::{log_event, shorten};
-use once_cell::sync::Lazy;
+use std::sync::atomic::{self, AtomicU64};
 use std::sync::RwLock;
 static MOJANG_INDEX: Lazy<RwLock<HashMap<String, u16>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static MOJANG_URLS: Lazy<RwLock<HashMap<String, String>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+static MOJANG_VERSIONS: Lazy<RwLock<Vec<McVersion>>> = Lazy::new(|| RwLock::new(Vec::new()));
 static BASE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d+(?:\.\d+)+").unwrap());
 static RC_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)-rc(\d+)").unwrap());
 static PRE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)-pre(\d+)").unwrap());
+static STAGE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(\d+(?:\.\d+)+)(?:[- ](pre-release|pre|rc|snapshot)[- ]?(\d*))?$").unwrap()
+});
+static WEEKLY_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\d{2})w(\d{2})([a-z])$").unwrap());
+// 编译进程序的正式版列表（截至 1.21.10），首次启动无网络时使用
+static BUNDLED_VERSIONS: Lazy<Vec<McVersion>> = Lazy::new(|| {
+    let manifest: MojangManifest =
+        serde_json::from_str(include_str!("../data/mc_versions.json")).unwrap();
+    manifest
+        .versions
+        .into_iter()
+        .map(|v| McVersion {
+            id: v.id,
+            kind: v.kind,
+            release_time: v.release_time,
+            parent_release: None,
+            java_version: None,
+        })
+        .collect()
+});
+static BUNDLED_INDEX: Lazy<HashMap<String, u16>> = Lazy::new(|| {
+    BUNDLED_VERSIONS
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.id.clone(), i as u16))
+        .collect()
+});
+// 周快照 → 所属正式版，随清单一起安装，避免排序时线性扫描
+static WEEKLY_PARENTS: Lazy<RwLock<WeeklyParents>> =
+    Lazy::new(|| RwLock::new(WeeklyParents::from_versions(&BUNDLED_VERSIONS)));
+// 退回旧缓存或内置列表的时间，0 表示已加载真实清单
+static FALLBACK_SINCE: AtomicU64 = AtomicU64::new(0);
+const FALLBACK_RETRY_MS: u64 = 60 * 1000;
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct MojangVersion {
     id: String,
+    #[serde(default)]
+    url: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    release_time: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct McVersion {
+    pub id: String,
+    // release / snapshot / old_beta / old_alpha
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub release_time: String,
+    pub parent_release: Option<String>,
+    // 需要读取版本 JSON，未获取前为空
+    pub java_version: Option<u32>,
 }
 
 #[derive(Deserialize)]
@@ -26,33 +79,137 @@ struct MojangManifest {
     versions: Vec<MojangVersion>,
 }
 
-fn cache_file() -> PathBuf {
-    cache_path("mc_versions.bin")
+#[derive(Serialize, Deserialize)]
+struct ManifestCache {
+    index: HashMap<String, u16>,
+    urls: HashMap<String, String>,
+    versions: Vec<McVersion>,
+    fetched_at: u64,
+    validators: Validators,
+}
+
+const CACHE_NAME: &str = "mc_versions.bin";
+const JAVA_CACHE_NAME: &str = "mc_versions-java.bin";
+
+fn read_manifest_cache() -> Option<ManifestCache> {
+    read_bincode(CACHE_NAME).ok()
+}
+
+fn write_manifest_cache(cache: &ManifestCache) -> anyhow::Result<()> {
+    write_bincode(CACHE_NAME, cache).context("Failed to write manifest cache")
+}
+
+fn install_manifest(cache: &ManifestCache) {
+    *MOJANG_URLS.write().unwrap() = cache.urls.clone();
+    install_versions(cache.index.clone(), cache.versions.clone());
+}
+
+fn install_versions(index: HashMap<String, u16>, versions: Vec<McVersion>) {
+    *WEEKLY_PARENTS.write().unwrap() = WeeklyParents::from_versions(&versions);
+    *MOJANG_INDEX.write().unwrap() = index;
+    *MOJANG_VERSIONS.write().unwrap() = versions;
+}
+
+// 真实清单不可用时依次使用磁盘缓存、内置列表
+fn install_fallback() {
+    match read_manifest_cache() {
+        Some(cache) => install_manifest(&cache),
+        None => install_versions(BUNDLED_INDEX.clone(), BUNDLED_VERSIONS.clone()),
+    }
+}
+
+#[derive(Default)]
+struct WeeklyParents {
+    // 清单中记录了 parent_release 的周快照
+    by_id: HashMap<String, Vec<u32>>,
+    // 正式版按发布的 ISO 周从旧到新排列
+    release_weeks: Vec<((u32, u32), Vec<u32>)>,
+}
+
+impl WeeklyParents {
+    fn from_versions(versions: &[McVersion]) -> WeeklyParents {
+        use chrono::Datelike;
+        let by_id = versions
+            .iter()
+            .filter(|v| WEEKLY_RE.is_match(&v.id))
+            .filter_map(|v| {
+                let parent = v.parent_release.as_deref().and_then(numeric_parts)?;
+                Some((v.id.clone(), parent))
+            })
+            .collect();
+        // 清单按时间倒序，反转后稳定排序，同一周内较早的正式版在前
+        let mut release_weeks: Vec<((u32, u32), Vec<u32>)> = versions
+            .iter()
+            .rev()
+            .filter(|v| v.kind == "release")
+            .filter_map(|v| {
+                let date = v.release_time.get(..10)?;
+                let w = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .ok()?
+                    .iso_week();
+                Some(((w.year() as u32, w.week()), numeric_parts(&v.id)?))
+            })
+            .collect();
+        release_weeks.sort_by_key(|(week, _)| *week);
+        WeeklyParents {
+            by_id,
+            release_weeks,
+        }
+    }
+}
+
+// 清单按时间倒序；预览版取版本号前缀，周快照取其后最近的正式版
+fn assign_parent_releases(versions: &mut [McVersion]) {
+    let mut newer_release: Option<String> = None;
+    for v in versions.iter_mut() {
+        if v.kind == "release" {
+            newer_release = Some(v.id.clone());
+            continue;
+        }
+        if v.kind != "snapshot" {
+            continue;
+        }
+        v.parent_release = BASE_RE
+            .find(&v.id)
+            .map(|m| m.as_str().to_string())
+            .or_else(|| newer_release.clone());
+    }
 }
 
 pub async fn refresh_manifest_cache_on_startup() -> anyhow::Result<()> {
-    const TTL_SECS: u64 = 7 * 24 * 60 * 60;
-    let path = cache_file();
-    if let Ok(meta) = fs::metadata(&path) {
-        if let Ok(modified) = meta.modified() {
-            if let Ok(age) = SystemTime::now().duration_since(modified) {
-                if age.as_secs() <= TTL_SECS {
-                    if let Ok(bytes) = fs::read(&path) {
-                        if let Ok(index) = deserialize::<HashMap<String, u16>>(&bytes) {
-                            let mut w = MOJANG_INDEX.write().unwrap();
-                            *w = index;
-                            return Ok(());
-                        }
-                    }
-                }
-            }
+    const TTL_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+    let cached = read_manifest_cache();
+    if let Some(cache) = &cached {
+        if now_millis().saturating_sub(cache.fetched_at) <= TTL_MS {
+            install_manifest(cache);
+            return Ok(());
         }
     }
+    if crate::util::is_offline() {
+        let cache =
+            cached.ok_or_else(|| crate::cache::offline_miss("Minecraft version manifest"))?;
+        install_manifest(&cache);
+        return Ok(());
+    }
     let client = crate::util::http_client()?;
     let url = "https://launchermeta.mojang.com/mc/game/version_manifest_v2.json";
-    let resp = crate::util::send_with_retry(client.get(url), 2)
+    let mut rb = client.get(url);
+    if let Some(cache) = &cached {
+        rb = cache.validators.apply(rb);
+    }
+    let resp = crate::util::send_with_retry(rb, 2)
         .await
         .context("Failed to fetch Mojang manifest")?;
+    if is_not_modified(&resp) {
+        if let Some(mut cache) = cached {
+            log_event("info", "mojang_manifest_not_modified");
+            cache.fetched_at = now_millis();
+            install_manifest(&cache);
+            write_manifest_cache(&cache)?;
+            return Ok(());
+        }
+    }
+    let validators = Validators::from_response(&resp);
     let status = resp.status();
     let body_text = resp
         .text()
@@ -82,169 +239,566 @@ pub async fn refresh_manifest_cache_on_startup() -> anyhow::Result<()> {
         ))
     })?;
     let mut map: HashMap<String, u16> = HashMap::new();
+    let mut urls: HashMap<String, String> = HashMap::new();
+    let mut versions: Vec<McVersion> = Vec::new();
     for (i, v) in manifest.versions.into_iter().enumerate() {
         if i <= u16::MAX as usize {
-            map.insert(v.id, i as u16);
+            map.insert(v.id.clone(), i as u16);
+        }
+        versions.push(McVersion {
+            id: v.id.clone(),
+            kind: v.kind,
+            release_time: v.release_time,
+            parent_release: None,
+            java_version: None,
+        });
+        if !v.url.is_empty() {
+            urls.insert(v.id, v.url);
         }
     }
-    {
-        let mut w = MOJANG_INDEX.write().unwrap();
-        *w = map.clone();
-    }
-    let data = serialize(&map)?;
-    fs::write(cache_file(), data).context("Failed to write manifest cache")?;
+    assign_parent_releases(&mut versions);
+    let cache = ManifestCache {
+        index: map,
+        urls,
+        versions,
+        fetched_at: now_millis(),
+        validators,
+    };
+    install_manifest(&cache);
+    write_manifest_cache(&cache)?;
     Ok(())
 }
 
-pub fn order_mc_versions(input: Vec<String>) -> Vec<String> {
-    {
-        let index = MOJANG_INDEX.read().unwrap();
-        if !index.is_empty() {
-            let mut items: Vec<(u16, String)> = input
-                .into_iter()
-                .map(|s| (index.get(&s).copied().unwrap_or(u16::MAX), s))
-                .collect();
-            items.sort_by(|a, b| a.0.cmp(&b.0));
-            let mut seen: HashSet<String> = HashSet::new();
-            let mut out = Vec::new();
-            for (_, s) in items {
-                if seen.insert(s.clone()) {
-                    out.push(s);
-                }
-            }
-            return out;
+#[derive(Deserialize)]
+struct VersionDownload {
+    url: String,
+    #[serde(default)]
+    size: u64,
+}
+
+#[derive(Deserialize, Default)]
+struct VersionDownloads {
+    client_mappings: Option<VersionDownload>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JavaVersion {
+    major_version: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionDetail {
+    #[serde(default)]
+    downloads: VersionDownloads,
+    java_version: Option<JavaVersion>,
+}
+
+async fn version_json_url(version: &str) -> anyhow::Result<String> {
+    if let Some(url) = MOJANG_URLS.read().unwrap().get(version) {
+        return Ok(url.clone());
+    }
+    refresh_manifest_cache_on_startup().await?;
+    MOJANG_URLS
+        .read()
+        .unwrap()
+        .get(version)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Unknown Minecraft version: {}", version))
+}
+
+fn read_java_cache() -> HashMap<String, u32> {
+    read_bincode(JAVA_CACHE_NAME).unwrap_or_default()
+}
+
+async fn fetch_version_detail(
+    client: &reqwest::Client,
+    version: &str,
+) -> anyhow::Result<VersionDetail> {
+    let url = version_json_url(version).await?;
+    let resp = crate::util::send_with_retry(client.get(&url), 2)
+        .await
+        .context("Failed to fetch Minecraft version JSON")?;
+    let status = resp.status();
+    let body_text = resp.text().await?;
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(format!(
+            "Mojang API Error: {} body {}",
+            status,
+            shorten(&body_text, 400)
+        )));
+    }
+    let detail: VersionDetail = serde_json::from_str(&body_text)
+        .map_err(|e| anyhow::anyhow!(format!("Mojang parse error: {}", e)))?;
+    // 顺带记录 Java 版本要求，版本 JSON 发布后不会再变
+    if let Some(java) = &detail.java_version {
+        let mut known = read_java_cache();
+        if known
+            .insert(version.to_string(), java.major_version)
+            .is_none()
+        {
+            write_bincode(JAVA_CACHE_NAME, &known).context("Failed to write Java version cache")?;
         }
     }
-    let path = cache_file();
-    let raw = fs::read(path);
-    if let Ok(bytes) = raw {
-        if let Ok(index) = deserialize::<HashMap<String, u16>>(&bytes) {
-            let mut items: Vec<(u16, String)> = input
-                .into_iter()
-                .map(|s| (index.get(&s).copied().unwrap_or(u16::MAX), s))
-                .collect();
-            items.sort_by(|a, b| a.0.cmp(&b.0));
-            let mut seen: HashSet<String> = HashSet::new();
-            let mut out = Vec::new();
-            for (_, s) in items {
-                if seen.insert(s.clone()) {
-                    out.push(s);
-                }
+    Ok(detail)
+}
+
+// 使用后备数据时每隔一段时间重新尝试获取真实清单
+async fn ensure_manifest_loaded() -> anyhow::Result<()> {
+    let since = FALLBACK_SINCE.load(atomic::Ordering::Relaxed);
+    let loaded = !MOJANG_VERSIONS.read().unwrap().is_empty();
+    if loaded && (since == 0 || now_millis().saturating_sub(since) < FALLBACK_RETRY_MS) {
+        return Ok(());
+    }
+    match refresh_manifest_cache_on_startup().await {
+        Ok(()) => FALLBACK_SINCE.store(0, atomic::Ordering::Relaxed),
+        Err(e) => {
+            if since == 0 {
+                log_event("warn", &format!("mojang_manifest_fallback {}", e));
             }
-            return out;
+            install_fallback();
+            FALLBACK_SINCE.store(now_millis().max(1), atomic::Ordering::Relaxed);
         }
     }
+    Ok(())
+}
+
+pub async fn list_versions(kinds: &[String]) -> anyhow::Result<Vec<McVersion>> {
+    ensure_manifest_loaded().await?;
+    let java = read_java_cache();
+    Ok(MOJANG_VERSIONS
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|v| kinds.is_empty() || kinds.iter().any(|k| k.eq_ignore_ascii_case(&v.kind)))
+        .map(|v| McVersion {
+            java_version: java.get(&v.id).copied(),
+            ..v.clone()
+        })
+        .collect())
+}
+
+pub async fn version_info(version: &str) -> anyhow::Result<McVersion> {
+    ensure_manifest_loaded().await?;
+    let mut info = MOJANG_VERSIONS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|v| v.id == version)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Unknown Minecraft version: {}", version))?;
+    info.java_version = read_java_cache().get(version).copied();
+    if info.java_version.is_none() && !crate::util::is_offline() {
+        let client = crate::util::http_client()?;
+        let detail = fetch_version_detail(&client, version).await?;
+        info.java_version = detail.java_version.map(|j| j.major_version);
+    }
+    Ok(info)
+}
+
+// 官方混淆映射按版本不会变化，缓存后不再过期
+pub async fn client_mappings(version: &str) -> anyhow::Result<String> {
+    let cache_name = format!(
+        "mappings-client-{}.bin",
+        crate::cache::safe_key_segment(version)
+    );
+    if let Ok(text) = read_bincode::<String>(&cache_name) {
+        return Ok(text);
+    }
+    if crate::util::is_offline() {
+        return Err(crate::cache::offline_miss(&format!(
+            "official mappings for {}",
+            version
+        )));
+    }
+    let client = crate::util::http_client()?;
+    let detail = fetch_version_detail(&client, version).await?;
+    let download = detail
+        .downloads
+        .client_mappings
+        .ok_or_else(|| anyhow::anyhow!("Minecraft {} has no official client mappings", version))?;
+    let resp = crate::util::send_with_retry(client.get(&download.url), 2)
+        .await
+        .context("Failed to download official client mappings")?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Official mappings download failed: {}",
+            resp.status()
+        ));
+    }
+    let text = resp.text().await?;
+    if download.size != 0 && text.len() as u64 != download.size {
+        return Err(anyhow::anyhow!(
+            "Official mappings size mismatch: expected {} got {}",
+            download.size,
+            text.len()
+        ));
+    }
+    log_event(
+        "info",
+        &format!(
+            "mojang_client_mappings_fetched {} bytes={}",
+            version,
+            text.len()
+        ),
+    );
+    write_bincode(&cache_name, &text).context("Failed to write mappings cache")?;
+    Ok(text)
+}
+
+// 清单尚未加载时先安装磁盘缓存或内置列表（只做一次，之后由 ensure_manifest_loaded 刷新）；
+// 索引相同或都不认识时按语义从新到旧
+fn order_by_index<K: Ord>(
+    input: Vec<String>,
+    key: impl Fn(&HashMap<String, u16>, &str) -> K,
+) -> Vec<String> {
+    if MOJANG_INDEX.read().unwrap().is_empty() {
+        install_fallback();
+        let _ = FALLBACK_SINCE.compare_exchange(
+            0,
+            1,
+            atomic::Ordering::Relaxed,
+            atomic::Ordering::Relaxed,
+        );
+    }
     let mut seen: HashSet<String> = HashSet::new();
-    let mut out = Vec::new();
-    for s in input {
-        if seen.insert(s.clone()) {
-            out.push(s);
+    let mut items: Vec<(K, String)> = Vec::new();
+    {
+        let index = MOJANG_INDEX.read().unwrap();
+        for s in input {
+            if seen.insert(s.clone()) {
+                items.push((key(&index, &s), s));
+            }
         }
     }
-    out
+    items.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| semantic_cmp(&b.1, &a.1)));
+    items.into_iter().map(|(_, s)| s).collect()
+}
+
+// 清单中没有的版本挂到所属正式版的位置，比清单中所有正式版都新的排在最前
+fn manifest_position(index: &HashMap<String, u16>, version: &str) -> u16 {
+    if let Some(i) = index.get(version) {
+        return *i;
+    }
+    let Some((parent, _, _)) = semantic_key(version) else {
+        return u16::MAX;
+    };
+    let parent_id = parent
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(".");
+    if let Some(i) = index.get(&parent_id) {
+        return *i;
+    }
+    let newest_release = index
+        .iter()
+        .filter(|(id, _)| {
+            BASE_RE
+                .find(id)
+                .map(|m| m.as_str() == id.as_str())
+                .unwrap_or(false)
+        })
+        .min_by_key(|(_, i)| **i)
+        .and_then(|(id, _)| numeric_parts(id));
+    match newest_release {
+        Some(newest) if parent > newest => 0,
+        _ => u16::MAX,
+    }
+}
+
+pub fn order_mc_versions(input: Vec<String>) -> Vec<String> {
+    order_by_index(input, manifest_position)
 }
 
 pub fn order_mc_versions_cf(input: Vec<String>) -> Vec<String> {
-    {
-        let index = MOJANG_INDEX.read().unwrap();
-        if !index.is_empty() {
-            let base_re = &BASE_RE;
-            let rc_re = &RC_RE;
-            let pre_re = &PRE_RE;
-            let mut items: Vec<(u16, u8, u16, String)> = Vec::new();
-            for s in input {
-                let sl = s.to_lowercase();
-                let mut idx = *index.get(&s).unwrap_or(&u16::MAX);
-                if idx == u16::MAX {
-                    if let Some(m) = base_re.find(&sl) {
-                        let base = &sl[m.start()..m.end()];
-                        if let Some(bi) = index.get(base) {
-                            idx = *bi;
-                        }
-                    }
-                }
-                let mut kind: u8 = 0;
-                let mut rank: u16 = 0;
-                if let Some(cap) = rc_re.captures(&sl) {
-                    kind = 1;
-                    rank = cap
-                        .get(1)
-                        .and_then(|g| g.as_str().parse::<u16>().ok())
-                        .map(|n| u16::MAX - n)
-                        .unwrap_or(u16::MAX);
-                } else if let Some(cap) = pre_re.captures(&sl) {
-                    kind = 2;
-                    rank = cap
-                        .get(1)
-                        .and_then(|g| g.as_str().parse::<u16>().ok())
-                        .map(|n| u16::MAX - n)
-                        .unwrap_or(u16::MAX);
-                } else if sl.contains("snapshot") {
-                    kind = 3;
+    order_by_index(input, |index, s| {
+        let sl = s.to_lowercase();
+        let idx = manifest_position(index, s);
+        let stage_rank = |cap: regex::Captures| {
+            cap.get(1)
+                .and_then(|g| g.as_str().parse::<u16>().ok())
+                .map(|n| u16::MAX - n)
+                .unwrap_or(u16::MAX)
+        };
+        let (kind, rank) = if let Some(cap) = RC_RE.captures(&sl) {
+            (1u8, stage_rank(cap))
+        } else if let Some(cap) = PRE_RE.captures(&sl) {
+            (2, stage_rank(cap))
+        } else if sl.contains("snapshot") {
+            (3, 0)
+        } else {
+            (0, 0)
+        };
+        (idx, kind, rank)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSpec {
+    Exact(String),
+    // 1.20.x / 1.20.*，只匹配该系列的正式版
+    Series(String),
+    Range(Vec<(Ordering, bool, String)>),
+}
+
+impl VersionSpec {
+    pub fn parse(s: &str) -> anyhow::Result<VersionSpec> {
+        let s = s.trim();
+        if s.contains(['<', '>', '=']) {
+            // 按运算符字符切分，允许 ">= 1.20.1 < 1.21"、">=1.20.1<1.21" 与逗号分隔
+            let mut tokens: Vec<(bool, String)> = Vec::new();
+            for c in s.chars() {
+                if c.is_whitespace() || c == ',' {
+                    tokens.push((false, String::new()));
+                    continue;
                 }
-                items.push((idx, kind, rank, s));
-            }
-            items.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
-            let mut seen: HashSet<String> = HashSet::new();
-            let mut out = Vec::new();
-            for (_, _, _, s) in items {
-                if seen.insert(s.clone()) {
-                    out.push(s);
+                let is_op = "<>=".contains(c);
+                match tokens.last_mut() {
+                    Some((op, text)) if *op == is_op && !text.is_empty() => text.push(c),
+                    _ => tokens.push((is_op, c.to_string())),
                 }
             }
-            return out;
-        }
-    }
-    let path = cache_file();
-    let raw = fs::read(path);
-    if let Ok(bytes) = raw {
-        if let Ok(index) = deserialize::<HashMap<String, u16>>(&bytes) {
-            let base_re = &BASE_RE;
-            let rc_re = &RC_RE;
-            let pre_re = &PRE_RE;
-            let mut items: Vec<(u16, u8, u16, String)> = Vec::new();
-            for s in input {
-                let sl = s.to_lowercase();
-                let mut idx = *index.get(&s).unwrap_or(&u16::MAX);
-                if idx == u16::MAX {
-                    if let Some(m) = base_re.find(&sl) {
-                        let base = &sl[m.start()..m.end()];
-                        if let Some(bi) = index.get(base) {
-                            idx = *bi;
-                        }
+            tokens.retain(|(_, text)| !text.is_empty());
+            let mut bounds = Vec::new();
+            let mut pending_op: Option<String> = None;
+            for (is_op, text) in tokens {
+                if is_op {
+                    if pending_op.replace(text).is_some() {
+                        return Err(anyhow::anyhow!(
+                            "Invalid version range {}: missing version",
+                            s
+                        ));
                     }
+                    continue;
                 }
-                let mut kind: u8 = 0;
-                let mut rank: u16 = 0;
-                if let Some(cap) = rc_re.captures(&sl) {
-                    kind = 1;
-                    rank = cap
-                        .get(1)
-                        .and_then(|g| g.as_str().parse::<u16>().ok())
-                        .map(|n| u16::MAX - n)
-                        .unwrap_or(u16::MAX);
-                } else if let Some(cap) = pre_re.captures(&sl) {
-                    kind = 2;
-                    rank = cap
-                        .get(1)
-                        .and_then(|g| g.as_str().parse::<u16>().ok())
-                        .map(|n| u16::MAX - n)
-                        .unwrap_or(u16::MAX);
-                } else if sl.contains("snapshot") {
-                    kind = 3;
+                let op = pending_op.take().unwrap_or_default();
+                let (dir, inclusive) = match op.as_str() {
+                    ">=" => (Ordering::Greater, true),
+                    ">" => (Ordering::Greater, false),
+                    "<=" => (Ordering::Less, true),
+                    "<" => (Ordering::Less, false),
+                    "" | "=" | "==" => (Ordering::Equal, true),
+                    other => {
+                        return Err(anyhow::anyhow!(
+                            "Invalid version range {}: unknown operator {}",
+                            s,
+                            other
+                        ))
+                    }
+                };
+                if !is_known_version(&text) {
+                    return Err(anyhow::anyhow!(
+                        "Invalid version range {}: unknown version {}",
+                        s,
+                        text
+                    ));
                 }
-                items.push((idx, kind, rank, s));
+                bounds.push((dir, inclusive, text));
             }
-            items.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
-            let mut seen: HashSet<String> = HashSet::new();
-            let mut out = Vec::new();
-            for (_, _, _, s) in items {
-                if seen.insert(s.clone()) {
-                    out.push(s);
-                }
+            if pending_op.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Invalid version range {}: missing version",
+                    s
+                ));
+            }
+            if bounds.is_empty() {
+                return Err(anyhow::anyhow!("Invalid version range {}: no bounds", s));
+            }
+            return Ok(VersionSpec::Range(bounds));
+        }
+        let series = s.strip_suffix(".x").or_else(|| s.strip_suffix(".*"));
+        Ok(match series {
+            Some(prefix) => VersionSpec::Series(prefix.to_string()),
+            None => VersionSpec::Exact(s.to_string()),
+        })
+    }
+
+    pub fn is_exact(&self) -> bool {
+        matches!(self, VersionSpec::Exact(_))
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            VersionSpec::Exact(v) => v == version,
+            VersionSpec::Series(prefix) => {
+                let is_release = BASE_RE
+                    .find(version)
+                    .map(|m| m.as_str() == version)
+                    .unwrap_or(false);
+                is_release
+                    && (version == prefix
+                        || version
+                            .strip_prefix(prefix.as_str())
+                            .map(|rest| rest.starts_with('.'))
+                            .unwrap_or(false))
             }
-            return out;
+            VersionSpec::Range(bounds) => bounds.iter().all(|(dir, inclusive, bound)| {
+                let ord = compare_mc_versions(version, bound);
+                ord == *dir || (*inclusive && ord == Ordering::Equal)
+            }),
+        }
+    }
+}
+
+// 范围边界必须是清单（或内置列表）中的版本，或能按语义解析的版本号
+fn is_known_version(version: &str) -> bool {
+    manifest_rank(version).is_some()
+        || BUNDLED_INDEX.contains_key(version)
+        || semantic_key(version).is_some()
+}
+
+fn manifest_rank(version: &str) -> Option<u16> {
+    MOJANG_INDEX.read().unwrap().get(version).copied()
+}
+
+fn numeric_parts(version: &str) -> Option<Vec<u32>> {
+    BASE_RE.find(version).map(|m| {
+        m.as_str()
+            .split('.')
+            .filter_map(|p| p.parse().ok())
+            .collect()
+    })
+}
+
+// 按发布先后比较，较新的版本为 Greater；清单中没有时退回按数字比较
+pub fn compare_mc_versions(a: &str, b: &str) -> Ordering {
+    if let (Some(ra), Some(rb)) = (manifest_rank(a), manifest_rank(b)) {
+        return rb.cmp(&ra);
+    }
+    semantic_cmp(a, b)
+}
+
+// 周快照归入其发布之后最近的正式版：优先用清单中的 parent_release，
+// 否则按清单（未加载时为内置列表）中正式版的发布周推算
+fn weekly_parent(version: &str, year: u32, week: u32) -> Option<Vec<u32>> {
+    let parents = WEEKLY_PARENTS.read().unwrap();
+    if let Some(parent) = parents.by_id.get(version) {
+        return Some(parent.clone());
+    }
+    let target = (2000 + year, week);
+    let i = parents.release_weeks.partition_point(|(w, _)| *w < target);
+    parents
+        .release_weeks
+        .get(i)
+        .map(|(_, parent)| parent.clone())
+}
+
+// (正式版号, 阶段, 序号)：阶段依次为快照、pre、rc、正式版
+fn semantic_key(version: &str) -> Option<(Vec<u32>, u8, u32)> {
+    if let Some(c) = WEEKLY_RE.captures(version) {
+        let (year, week) = (c[1].parse().ok()?, c[2].parse().ok()?);
+        let letter = c[3].as_bytes()[0] - b'a';
+        let parent = weekly_parent(version, year, week).unwrap_or_else(|| vec![u32::MAX]);
+        return Some((parent, 0, (year * 100 + week) * 26 + letter as u32));
+    }
+    let c = STAGE_RE.captures(version)?;
+    let nums = c[1].split('.').filter_map(|p| p.parse().ok()).collect();
+    let n = c.get(3).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+    let stage = match c.get(2).map(|m| m.as_str().to_lowercase()) {
+        None => 3,
+        Some(s) if s == "rc" => 2,
+        Some(s) if s == "snapshot" => 0,
+        Some(_) => 1,
+    };
+    Some((nums, stage, n))
+}
+
+// 不依赖清单的确定性排序，较新的版本为 Greater；无法识别的版本视为最旧，彼此按字面排序
+pub fn semantic_cmp(a: &str, b: &str) -> Ordering {
+    semantic_key(a).cmp(&semantic_key(b)).then_with(|| a.cmp(b))
+}
+
+fn release_line(version: &str) -> Option<Vec<u32>> {
+    numeric_parts(version).map(|p| p.into_iter().take(2).collect())
+}
+
+// 同一系列内距离目标最近的版本，距离相同时取较旧的
+pub fn nearest_compatible<'a>(
+    target: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let line = release_line(target)?;
+    let distance = |v: &str| match (manifest_rank(target), manifest_rank(v)) {
+        (Some(a), Some(b)) => a.abs_diff(b) as u32,
+        _ => {
+            let (pa, pb) = (
+                numeric_parts(target).unwrap_or_default(),
+                numeric_parts(v).unwrap_or_default(),
+            );
+            pa.get(2)
+                .copied()
+                .unwrap_or(0)
+                .abs_diff(pb.get(2).copied().unwrap_or(0))
+        }
+    };
+    candidates
+        .filter(|v| release_line(v).as_ref() == Some(&line))
+        .min_by(|a, b| {
+            distance(a)
+                .cmp(&distance(b))
+                .then_with(|| compare_mc_versions(a, b))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_bounds() {
+        let expected = VersionSpec::Range(vec![
+            (Ordering::Greater, true, "1.20.1".to_string()),
+            (Ordering::Less, false, "1.21".to_string()),
+        ]);
+        for text in [
+            ">=1.20.1 <1.21",
+            ">= 1.20.1 < 1.21",
+            ">=1.20.1<1.21",
+            ">=1.20.1,<1.21",
+        ] {
+            assert_eq!(VersionSpec::parse(text).unwrap(), expected, "{}", text);
+        }
+        assert_eq!(
+            VersionSpec::parse("1.20.x").unwrap(),
+            VersionSpec::Series("1.20".to_string())
+        );
+        assert!(VersionSpec::parse("1.20.1").unwrap().is_exact());
+    }
+
+    #[test]
+    fn parse_range_rejects_bad_bounds() {
+        for text in [">=abc", ">=1.20.1 <", ">= <1.21", "=>1.20", ">=1.20 <foo"] {
+            assert!(VersionSpec::parse(text).is_err(), "{}", text);
         }
+        assert!(VersionSpec::parse(">=23w13a <1.20-pre1").is_ok());
+    }
+
+    #[test]
+    fn range_matches_semantically() {
+        let spec = VersionSpec::parse(">=1.20.1 <1.21").unwrap();
+        assert!(spec.matches("1.20.1"));
+        assert!(spec.matches("1.20.6"));
+        assert!(!spec.matches("1.20"));
+        assert!(!spec.matches("1.21"));
+        let series = VersionSpec::parse("1.20.x").unwrap();
+        assert!(series.matches("1.20") && series.matches("1.20.4"));
+        assert!(!series.matches("1.20.5-rc1") && !series.matches("1.21"));
+    }
+
+    #[test]
+    fn weekly_snapshots_follow_their_release() {
+        let parents = WeeklyParents::from_versions(&BUNDLED_VERSIONS);
+        let i = parents
+            .release_weeks
+            .partition_point(|(w, _)| *w < (2023, 13));
+        assert_eq!(parents.release_weeks[i].1, vec![1, 20]);
+        let ordered = order_mc_versions(
+            ["1.19.4", "23w13a", "1.20.1", "1.20"]
+                .map(String::from)
+                .to_vec(),
+        );
+        assert_eq!(ordered, ["1.20.1", "1.20", "23w13a", "1.19.4"]);
     }
-    input
 }
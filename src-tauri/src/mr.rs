@@ -1,15 +1,27 @@
-use crate::cache::{now_millis, read_bincode, write_bincode};
-use crate::util::{log_event, shorten};
+use crate::cache::{
+    is_not_modified, now_millis, offline_miss, read_bincode, write_bincode, Validators,
+};
+use crate::util::{is_offline, log_event, shorten};
 use anyhow::{anyhow, Context};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 struct MrProjectBrief {
     title: String,
     icon_url: Option<String>,
 }
 
+fn mr_brief_cache_name(project_slug: &str) -> String {
+    let key = crate::cache::safe_key_segment(project_slug);
+    format!("mr-brief-{}.bin", key)
+}
+
 pub async fn get_mr_mod_brief(project_slug: &str) -> anyhow::Result<(String, Option<String>)> {
+    if is_offline() {
+        let proj: MrProjectBrief = read_bincode(&mr_brief_cache_name(project_slug))
+            .map_err(|_| offline_miss(&format!("Modrinth project {}", project_slug)))?;
+        return Ok((proj.title, proj.icon_url));
+    }
     let client = crate::util::http_client()?;
     let url = format!("https://api.modrinth.com/v2/project/{}", project_slug);
     let resp = crate::util::send_with_retry(client.get(&url), 2)
@@ -43,6 +55,7 @@ pub async fn get_mr_mod_brief(project_slug: &str) -> anyhow::Result<(String, Opt
             shorten(&body_text, 400)
         ))
     })?;
+    let _ = write_bincode(&mr_brief_cache_name(project_slug), &proj);
     log_event(
         "info",
         &format!(
@@ -68,6 +81,7 @@ pub struct MrVersion {
 struct MrVersionCache {
     versions: Vec<MrVersion>,
     fetched_at: u64,
+    validators: Validators,
 }
 
 fn mr_cache_name(project_slug: &str) -> String {
@@ -75,11 +89,37 @@ fn mr_cache_name(project_slug: &str) -> String {
     format!("mr-versions-{}.bin", key)
 }
 
+// 某个项目的全部缓存条目（文件名前缀）
+pub fn project_cache_prefixes(project_slug: &str) -> Vec<String> {
+    vec![
+        mr_cache_name(project_slug),
+        mr_brief_cache_name(project_slug),
+    ]
+}
+
 async fn fetch_versions(url: &str) -> anyhow::Result<Vec<MrVersion>> {
+    let (versions, _) = fetch_versions_conditional(url, None)
+        .await?
+        .ok_or_else(|| anyhow!("Modrinth returned 304 for an unconditional request"))?;
+    Ok(versions)
+}
+
+async fn fetch_versions_conditional(
+    url: &str,
+    validators: Option<&Validators>,
+) -> anyhow::Result<Option<(Vec<MrVersion>, Validators)>> {
     let client = crate::util::http_client()?;
-    let resp = crate::util::send_with_retry(client.get(url), 2)
+    let mut rb = client.get(url);
+    if let Some(v) = validators {
+        rb = v.apply(rb);
+    }
+    let resp = crate::util::send_with_retry(rb, 2)
         .await
         .context("Failed to connect to Modrinth API")?;
+    if is_not_modified(&resp) {
+        return Ok(None);
+    }
+    let fresh_validators = Validators::from_response(&resp);
     let status = resp.status();
     let body_text = resp
         .text()
@@ -108,7 +148,7 @@ async fn fetch_versions(url: &str) -> anyhow::Result<Vec<MrVersion>> {
             shorten(&body_text, 400)
         ))
     })?;
-    Ok(versions)
+    Ok(Some((versions, fresh_validators)))
 }
 
 async fn fetch_and_store_versions(project_slug: &str) -> anyhow::Result<Vec<MrVersion>> {
@@ -116,49 +156,127 @@ async fn fetch_and_store_versions(project_slug: &str) -> anyhow::Result<Vec<MrVe
         "https://api.modrinth.com/v2/project/{}/version",
         project_slug
     );
-    let versions = fetch_versions(&url).await?;
-    let cache = MrVersionCache {
-        versions: versions.clone(),
-        fetched_at: now_millis(),
+    let cached = load_versions_from_cache(project_slug).await;
+    let fetched = fetch_versions_conditional(&url, cached.as_ref().map(|c| &c.validators)).await?;
+    let cache = match (fetched, cached) {
+        (Some((versions, validators)), _) => MrVersionCache {
+            versions,
+            fetched_at: now_millis(),
+            validators,
+        },
+        (None, Some(mut cache)) => {
+            log_event(
+                "info",
+                &format!("mr_versions_not_modified {}", project_slug),
+            );
+            cache.fetched_at = now_millis();
+            cache
+        }
+        (None, None) => {
+            return Err(anyhow!(
+                "Modrinth returned 304 but no cached versions exist for {}",
+                project_slug
+            ))
+        }
     };
     write_bincode(&mr_cache_name(project_slug), &cache)?;
-    Ok(versions)
+    Ok(cache.versions)
 }
 
 async fn load_versions_from_cache(project_slug: &str) -> Option<MrVersionCache> {
     read_bincode(&mr_cache_name(project_slug)).ok()
 }
 
+async fn load_versions_offline(project_slug: &str) -> anyhow::Result<Vec<MrVersion>> {
+    load_versions_from_cache(project_slug)
+        .await
+        .map(|cache| cache.versions)
+        .ok_or_else(|| offline_miss(&format!("Modrinth versions of {}", project_slug)))
+}
+
 pub async fn get_latest_mr_version(
     project_slug: &str,
     mc_version: &str,
     loader: &str,
-) -> anyhow::Result<(Option<String>, Option<String>, Option<String>)> {
-    let mut versions = fetch_and_store_versions(project_slug).await?;
+    max_release_level: u8,
+    fallback: bool,
+) -> anyhow::Result<(
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+)> {
+    let mut versions = if is_offline() {
+        load_versions_offline(project_slug).await?
+    } else {
+        fetch_and_store_versions(project_slug).await?
+    };
     versions.sort_by(|a, b| b.date_published.cmp(&a.date_published));
     let priority_order = ["release", "beta", "alpha"];
     let loader_lower = loader.to_lowercase();
-    for vtype in priority_order {
-        for ver in &versions {
-            if ver.version_type != vtype {
+    let loader_lower = loader_lower.as_str();
+    let spec = crate::mojang::VersionSpec::parse(mc_version)?;
+    let found = |ver: &MrVersion, game_version: &str| {
+        Ok((
+            Some(ver.id.clone()),
+            Some(ver.version_number.clone()),
+            Some(ver.version_type.clone()),
+            Some(game_version.to_string()),
+        ))
+    };
+    let candidates = |vtype: &'static str| {
+        versions.iter().filter(move |ver| {
+            ver.version_type == vtype
+                && ver.loaders.iter().any(|l| l.to_lowercase() == loader_lower)
+        })
+    };
+    // 范围匹配时先取满足条件的最新 Minecraft 版本，同一版本再取最新发布的文件（与 CurseForge 一致）
+    for vtype in priority_order.into_iter().take(max_release_level as usize) {
+        let mut best: Option<(&MrVersion, &String)> = None;
+        for ver in candidates(vtype) {
+            let matched = ver
+                .game_versions
+                .iter()
+                .filter(|v| spec.matches(v))
+                .max_by(|a, b| crate::mojang::compare_mc_versions(a, b));
+            let Some(game_version) = matched else {
                 continue;
+            };
+            // versions 已按发布时间从新到旧排列，版本相同时保留先出现的
+            let newer = best
+                .map(|(_, g)| crate::mojang::compare_mc_versions(game_version, g).is_gt())
+                .unwrap_or(true);
+            if newer {
+                best = Some((ver, game_version));
             }
-            if ver.game_versions.contains(&mc_version.to_string())
-                && ver.loaders.iter().any(|l| l.to_lowercase() == loader_lower)
-            {
-                return Ok((
-                    Some(ver.id.clone()),
-                    Some(ver.version_number.clone()),
-                    Some(ver.version_type.clone()),
-                ));
+        }
+        if let Some((ver, game_version)) = best {
+            return found(ver, game_version);
+        }
+    }
+    if fallback && spec.is_exact() {
+        for vtype in priority_order.into_iter().take(max_release_level as usize) {
+            let nearest = crate::mojang::nearest_compatible(
+                mc_version,
+                candidates(vtype).flat_map(|ver| ver.game_versions.iter().map(String::as_str)),
+            );
+            if let Some(game_version) = nearest {
+                if let Some(ver) = candidates(vtype)
+                    .find(|ver| ver.game_versions.iter().any(|v| v == game_version))
+                {
+                    return found(ver, game_version);
+                }
             }
         }
     }
-    Ok((None, None, None))
+    Ok((None, None, None, None))
 }
 
 pub async fn get_versions(project_slug: &str, use_cache: bool) -> anyhow::Result<Vec<MrVersion>> {
     const TTL_MS: u64 = 6 * 60 * 60 * 1000;
+    if is_offline() {
+        return load_versions_offline(project_slug).await;
+    }
     if use_cache {
         if let Some(cache) = load_versions_from_cache(project_slug).await {
             let age = now_millis().saturating_sub(cache.fetched_at);
@@ -180,7 +298,7 @@ pub async fn get_versions_filtered(
     loader: &str,
     use_cache: bool,
 ) -> anyhow::Result<Vec<MrVersion>> {
-    if use_cache {
+    if use_cache || is_offline() {
         let all = get_versions(project_slug, true).await?;
         let loader_lower = loader.to_lowercase();
         let mc = mc_version.to_string();
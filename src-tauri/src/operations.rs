@@ -13,7 +13,7 @@ use crate::gradle::{
 };
 use crate::mojang::{order_mc_versions, order_mc_versions_cf};
 use crate::mr::{get_latest_mr_version, get_mr_mod_brief, get_versions, get_versions_filtered};
-use crate::util::app_data_dir;
+use crate::util::{app_data_dir, is_offline};
 
 #[derive(Serialize)]
 struct VersionChoice {
@@ -32,14 +32,17 @@ struct BatchModBrief {
 
 #[tauri::command]
 pub async fn list_versions(
-    source: String,
+    source: Option<String>,
     project_id: String,
-    mc_version: String,
-    loader: String,
+    mc_version: Option<String>,
+    loader: Option<String>,
     cf_api_key: Option<String>,
     use_cache: Option<bool>,
 ) -> Result<serde_json::Value, String> {
     let res = || async {
+        let source = crate::settings::resolve_source(source.clone())?;
+        let mc_version = crate::settings::resolve_mc_version(mc_version.clone())?;
+        let loader = crate::settings::resolve_loader(loader.clone())?;
         let use_cache = use_cache.unwrap_or(false);
         if source.to_lowercase() == "curseforge" {
             let api_key = crate::util::resolve_cf_api_key(cf_api_key.clone())?;
@@ -64,7 +67,7 @@ pub async fn list_versions(
                         kind: level.to_string(),
                     });
                 }
-                Ok(json!({"choices": choices}))
+                Ok(json!({"choices": choices, "stale": is_offline()}))
             } else {
                 let indexes = get_cf_latest_indexes(pid, &api_key).await?;
                 let target_loader: String = crate::util::loader_name_to_tag(&loader);
@@ -90,7 +93,7 @@ pub async fn list_versions(
                 }
                 items.sort_by(|a, b| a.0.cmp(&b.0));
                 let choices: Vec<VersionChoice> = items.into_iter().map(|(_, c)| c).collect();
-                Ok(json!({"choices": choices}))
+                Ok(json!({"choices": choices, "stale": is_offline()}))
             }
         } else if source.to_lowercase() == "modrinth" {
             let mut versions =
@@ -111,21 +114,25 @@ pub async fn list_versions(
                     kind: v.version_type,
                 });
             }
-            Ok(json!({"choices": choices}))
+            Ok(json!({"choices": choices, "stale": is_offline()}))
         } else {
             Err(anyhow!("Unknown source: {}", source))
         }
     };
-    res().await.map_err(|e| e.to_string())
+    crate::logging::with_operation("list_versions", res())
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn get_batch_mod_briefs(
-    source: String,
+    source: Option<String>,
     items: Vec<String>,
     cf_api_key: Option<String>,
 ) -> Result<serde_json::Value, String> {
     let res = || async {
+        let source = crate::settings::resolve_source(source.clone())?;
+        let concurrency = crate::settings::current().concurrency.max(1);
         let mut mods: Vec<BatchModBrief> = Vec::new();
         if source.to_lowercase() == "curseforge" {
             let api_key = crate::util::resolve_cf_api_key(cf_api_key.clone())?;
@@ -153,7 +160,7 @@ pub async fn get_batch_mod_briefs(
                     })
                 }
             });
-            let mut stream = stream::iter(tasks).buffer_unordered(4);
+            let mut stream = stream::iter(tasks).buffer_unordered(concurrency);
             while let Some(res) = stream.next().await {
                 match res {
                     Ok(b) => mods.push(b),
@@ -181,7 +188,7 @@ pub async fn get_batch_mod_briefs(
                     icon_data,
                 })
             });
-            let mut stream = stream::iter(tasks).buffer_unordered(4);
+            let mut stream = stream::iter(tasks).buffer_unordered(concurrency);
             while let Some(res) = stream.next().await {
                 match res {
                     Ok(b) => mods.push(b),
@@ -191,9 +198,24 @@ pub async fn get_batch_mod_briefs(
         } else {
             return Err(anyhow!("Unknown source: {}", source));
         }
-        Ok(json!({"mods": mods}))
+        Ok(json!({"mods": mods, "stale": is_offline()}))
     };
-    res().await.map_err(|e| e.to_string())
+    crate::logging::with_operation("get_batch_mod_briefs", res())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn fallback_message(requested: &str, matched: Option<&str>) -> String {
+    match matched {
+        Some(v)
+            if crate::mojang::VersionSpec::parse(requested)
+                .map(|spec| !spec.matches(v))
+                .unwrap_or(false) =>
+        {
+            format!("⚠ No build for MC {}, using nearest MC {}\n", requested, v)
+        }
+        _ => String::new(),
+    }
 }
 
 async fn process_update(
@@ -211,14 +233,27 @@ async fn process_update(
     let mut gradle_content = fs::read_to_string(gradle_path)
         .await
         .context("Could not read build.gradle")?;
+    let stale_msg = if is_offline() {
+        "⚠ Offline mode: cached data used, may be stale\n"
+    } else {
+        ""
+    };
     if source.to_lowercase() == "curseforge" {
         let api_key = crate::util::resolve_cf_api_key(cf_api_key.clone())?;
         let pid = project_id
             .parse::<u32>()
             .context("Project ID must be a number for CurseForge")?;
         let (slug, modid_num) = get_project_meta(pid, &api_key).await?;
-        let (file_id, version, level) =
-            get_latest_cf_file(pid, &mc_version, &loader, &api_key).await?;
+        let settings = crate::settings::current();
+        let (file_id, version, level, matched) = get_latest_cf_file(
+            pid,
+            &mc_version,
+            &loader,
+            &api_key,
+            settings.max_release_level(),
+            settings.nearest_version_fallback,
+        )
+        .await?;
         let file_id = file_id.ok_or_else(|| {
             anyhow!(
                 "No matching CurseForge file found for MC {} / {}",
@@ -231,6 +266,7 @@ async fn process_update(
             Some(3) => "⚠ Alpha Build used\n",
             _ => "",
         };
+        let fallback_msg = fallback_message(&mc_version, matched.as_deref());
         gradle_content = ensure_curse_maven_repo(&gradle_content);
         let dep_line = generate_dep(&loader, &slug, &modid_num.to_string(), file_id)?;
         gradle_content =
@@ -239,15 +275,24 @@ async fn process_update(
             .await
             .context("Failed to write build.gradle")?;
         Ok(format!(
-            "{}✅ Updated Dependency: {}\n🎉 New Version: {} (File ID: {})",
+            "{}{}{}✅ Updated Dependency: {}\n🎉 New Version: {} (File ID: {})",
+            stale_msg,
             level_msg,
+            fallback_msg,
             dep_line,
             version.unwrap_or_default(),
             file_id
         ))
     } else if source.to_lowercase() == "modrinth" {
-        let (ver_id, version, level) =
-            get_latest_mr_version(&project_id, &mc_version, &loader).await?;
+        let settings = crate::settings::current();
+        let (ver_id, version, level, matched) = get_latest_mr_version(
+            &project_id,
+            &mc_version,
+            &loader,
+            settings.max_release_level(),
+            settings.nearest_version_fallback,
+        )
+        .await?;
         let ver_id = ver_id.ok_or_else(|| {
             anyhow!(
                 "No matching Modrinth version found for MC {} / {}",
@@ -260,6 +305,7 @@ async fn process_update(
             Some("alpha") => "⚠ Alpha Build used\n",
             _ => "",
         };
+        let fallback_msg = fallback_message(&mc_version, matched.as_deref());
         gradle_content = ensure_modrinth_maven_repo(&gradle_content);
         let dep_line = generate_mr_dep(&loader, &project_id, &ver_id)?;
         gradle_content = update_or_insert_dependency_mr(&gradle_content, &project_id, &dep_line);
@@ -267,8 +313,10 @@ async fn process_update(
             .await
             .context("Failed to write build.gradle")?;
         Ok(format!(
-            "{}✅ Updated Dependency: {}\n🎉 New Version: {} (Version ID: {})",
+            "{}{}{}✅ Updated Dependency: {}\n🎉 New Version: {} (Version ID: {})",
+            stale_msg,
             level_msg,
+            fallback_msg,
             dep_line,
             version.unwrap_or_default(),
             ver_id
@@ -282,33 +330,39 @@ async fn process_update(
 pub async fn update_dependency(
     gradle_path: String,
     project_id: String,
-    mc_version: String,
-    loader: String,
-    source: String,
+    mc_version: Option<String>,
+    loader: Option<String>,
+    source: Option<String>,
     cf_api_key: Option<String>,
 ) -> Result<String, String> {
-    process_update(
-        gradle_path,
-        project_id,
-        mc_version,
-        loader,
-        source,
-        cf_api_key,
-    )
-    .await
-    .map_err(|e| e.to_string())
+    let res = || async {
+        process_update(
+            gradle_path.clone(),
+            project_id.clone(),
+            crate::settings::resolve_mc_version(mc_version.clone())?,
+            crate::settings::resolve_loader(loader.clone())?,
+            crate::settings::resolve_source(source.clone())?,
+            cf_api_key.clone(),
+        )
+        .await
+    };
+    crate::logging::with_operation("update_dependency", res())
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn apply_selected_version(
     gradle_path: String,
-    source: String,
+    source: Option<String>,
     project_id: String,
-    loader: String,
+    loader: Option<String>,
     selected_id: String,
     cf_api_key: Option<String>,
 ) -> Result<String, String> {
     let res = || async {
+        let source = crate::settings::resolve_source(source.clone())?;
+        let loader = crate::settings::resolve_loader(loader.clone())?;
         let gradle_path_p = Path::new(&gradle_path);
         if !gradle_path_p.exists() {
             return Err(anyhow!(
@@ -355,16 +409,19 @@ pub async fn apply_selected_version(
             Err(anyhow!("Unknown source: {}", source))
         }
     };
-    res().await.map_err(|e| e.to_string())
+    crate::logging::with_operation("apply_selected_version", res())
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn get_project_options(
-    source: String,
+    source: Option<String>,
     project_id: String,
     cf_api_key: Option<String>,
 ) -> Result<serde_json::Value, String> {
     let res = || async {
+        let source = crate::settings::resolve_source(source.clone())?;
         let use_cache = true;
         if source.to_lowercase() == "curseforge" {
             let api_key = crate::util::resolve_cf_api_key(cf_api_key.clone())?;
@@ -409,7 +466,7 @@ pub async fn get_project_options(
                 .map(|(k, v)| (k, order_mc_versions_cf(v.into_iter().collect::<Vec<_>>())))
                 .collect::<std::collections::BTreeMap<_, _>>();
             Ok(
-                json!({"versions": versions, "loaders": loaders, "id": pid, "version_to_loaders": v2l_vec, "loader_to_versions": l2v_vec}),
+                json!({"versions": versions, "loaders": loaders, "id": pid, "version_to_loaders": v2l_vec, "loader_to_versions": l2v_vec, "stale": is_offline()}),
             )
         } else if source.to_lowercase() == "modrinth" {
             let versions = get_versions(&project_id, use_cache).await?;
@@ -452,36 +509,40 @@ pub async fn get_project_options(
                 .map(|(k, v)| (k, order_mc_versions(v.into_iter().collect::<Vec<_>>())))
                 .collect::<std::collections::BTreeMap<_, _>>();
             Ok(
-                json!({"versions": versions, "loaders": loaders, "slug": project_id, "version_to_loaders": v2l_vec, "loader_to_versions": l2v_vec}),
+                json!({"versions": versions, "loaders": loaders, "slug": project_id, "version_to_loaders": v2l_vec, "loader_to_versions": l2v_vec, "stale": is_offline()}),
             )
         } else {
             Err(anyhow!("Unknown source: {}", source))
         }
     };
-    res().await.map_err(|e| e.to_string())
+    crate::logging::with_operation("get_project_options", res())
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn update_dependencies_batch(
     gradle_path: String,
-    source: String,
+    source: Option<String>,
     items: Vec<String>,
-    mc_version: String,
-    loader: String,
+    mc_version: Option<String>,
+    loader: Option<String>,
     cf_api_key: Option<String>,
 ) -> Result<String, String> {
+    let source = crate::settings::resolve_source(source).map_err(|e| e.to_string())?;
+    let mc_version = crate::settings::resolve_mc_version(mc_version).map_err(|e| e.to_string())?;
+    let loader = crate::settings::resolve_loader(loader).map_err(|e| e.to_string())?;
     let mut out = String::new();
     for item in items.into_iter() {
-        match process_update(
+        let update = process_update(
             gradle_path.clone(),
             item.clone(),
             mc_version.clone(),
             loader.clone(),
             source.clone(),
             cf_api_key.clone(),
-        )
-        .await
-        {
+        );
+        match crate::logging::with_operation("update_dependency", update).await {
             Ok(res) => out.push_str(&format!("\n[{}] {}\n", item, res)),
             Err(err) => out.push_str(&format!("\n[{}] ❌ {}\n", item, err)),
         }
@@ -497,12 +558,11 @@ pub async fn save_log(content: String) -> Result<String, String> {
     }
     let ts = Local::now().format("%Y%m%d-%H%M%S").to_string();
     let path = base.join(format!("log-{}.txt", ts));
-    let backend_path = base.join("runtime.log");
-    let backend_content = std::fs::read_to_string(&backend_path).unwrap_or_default();
-    let combined = format!(
+    let backend_content = crate::logging::read_backend_log();
+    let combined = crate::logging::redact(&format!(
         "[FRONTEND]\n{}\n\n[BACKEND]\n{}\n",
         content, backend_content
-    );
+    ));
     std::fs::write(&path, combined).map_err(|e| e.to_string())?;
     Ok(path.to_string_lossy().into())
 }
@@ -512,6 +572,58 @@ pub async fn clear_all_caches() -> Result<(), String> {
     crate::cache::clear_all_cache().map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_cache_stats() -> Result<serde_json::Value, String> {
+    let categories = crate::cache::cache_stats();
+    let total_bytes: u64 = categories.iter().map(|c| c.bytes).sum();
+    let total_entries: usize = categories.iter().map(|c| c.entries).sum();
+    // max_bytes 只约束 limited_bytes，unlimited_categories 中的分类不计入
+    let limited_bytes: u64 = categories
+        .iter()
+        .filter(|c| c.counts_toward_limit)
+        .map(|c| c.bytes)
+        .sum();
+    Ok(json!({
+        "categories": categories,
+        "total_bytes": total_bytes,
+        "total_entries": total_entries,
+        "limited_bytes": limited_bytes,
+        "max_bytes": crate::cache::max_cache_bytes(),
+        "unlimited_categories": crate::cache::UNLIMITED_CATEGORIES
+    }))
+}
+
+#[tauri::command]
+pub async fn invalidate_cache_category(category: String) -> Result<usize, String> {
+    crate::cache::invalidate_category(&category).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn invalidate_project_cache(source: String, project_id: String) -> Result<usize, String> {
+    let prefixes = match source.to_lowercase().as_str() {
+        "curseforge" => {
+            let id = project_id
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| "Project ID must be a number for CurseForge".to_string())?;
+            crate::cf::project_cache_prefixes(id)
+        }
+        "modrinth" => crate::mr::project_cache_prefixes(project_id.trim()),
+        other => return Err(format!("Unknown source: {}", other)),
+    };
+    crate::cache::invalidate_prefixes(&prefixes).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_cache_max_size(max_bytes: u64) -> Result<(), String> {
+    crate::settings::update(crate::settings::SettingsPatch {
+        max_cache_bytes: Some(max_bytes),
+        ..Default::default()
+    })
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn refresh_mojang_cache() -> Result<(), String> {
     crate::mojang::refresh_manifest_cache_on_startup()
@@ -519,6 +631,63 @@ pub async fn refresh_mojang_cache() -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
+// 未指定类型时只返回正式版，快照需由界面显式请求
+#[tauri::command]
+pub async fn list_mc_versions(types: Option<Vec<String>>) -> Result<serde_json::Value, String> {
+    let types = types.unwrap_or_else(|| vec!["release".to_string()]);
+    let versions = crate::mojang::list_versions(&types)
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::to_value(versions).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_mc_version_info(version: String) -> Result<serde_json::Value, String> {
+    let info = crate::mojang::version_info(&version)
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::to_value(info).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_offline_mode(enabled: bool) -> Result<bool, String> {
+    crate::settings::update(crate::settings::SettingsPatch {
+        offline: Some(enabled),
+        ..Default::default()
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(is_offline())
+}
+
+#[tauri::command]
+pub async fn get_offline_mode() -> Result<bool, String> {
+    Ok(is_offline())
+}
+
+#[tauri::command]
+pub async fn get_settings() -> Result<serde_json::Value, String> {
+    let settings = crate::settings::current();
+    let mut v = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+    v["cf_api_key_storage"] = json!(crate::settings::cf_api_key_storage());
+    v["cf_api_key_set"] = json!(crate::settings::stored_cf_api_key().is_some());
+    Ok(v)
+}
+
+#[tauri::command]
+pub async fn set_settings(
+    patch: crate::settings::SettingsPatch,
+) -> Result<serde_json::Value, String> {
+    crate::settings::update(patch).map_err(|e| e.to_string())?;
+    get_settings().await
+}
+
+#[tauri::command]
+pub async fn set_cf_api_key(key: Option<String>) -> Result<String, String> {
+    crate::settings::set_cf_api_key(key)
+        .map(|storage| storage.to_string())
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_log_dir() -> Result<String, String> {
     let base = app_data_dir().join("logs");
@@ -531,12 +700,14 @@ pub async fn get_log_dir() -> Result<String, String> {
 #[tauri::command]
 pub async fn apply_selected_versions_batch(
     gradle_path: String,
-    source: String,
+    source: Option<String>,
     selections: Vec<(String, String)>,
-    loader: String,
+    loader: Option<String>,
     cf_api_key: Option<String>,
 ) -> Result<String, String> {
     let res = || async {
+        let source = crate::settings::resolve_source(source.clone())?;
+        let loader = crate::settings::resolve_loader(loader.clone())?;
         let gradle_path_p = Path::new(&gradle_path);
         if !gradle_path_p.exists() {
             return Err(anyhow!(format!(
@@ -581,5 +752,7 @@ pub async fn apply_selected_versions_batch(
             .context("Failed to write build.gradle")?;
         Ok(summary)
     };
-    res().await.map_err(|e| e.to_string())
+    crate::logging::with_operation("apply_selected_versions_batch", res())
+        .await
+        .map_err(|e| e.to_string())
 }
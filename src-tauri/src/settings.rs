@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Context};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::util::{app_data_dir, log_event};
+
+const KEYRING_SERVICE: &str = "ModDependencyUpdater";
+const KEYRING_USER: &str = "curseforge-api-key";
+
+static SETTINGS: Lazy<RwLock<Settings>> = Lazy::new(|| RwLock::new(load()));
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub default_mc_version: Option<String>,
+    pub default_loader: Option<String>,
+    pub default_source: Option<String>,
+    pub release_channel: String,
+    pub concurrency: usize,
+    pub offline: bool,
+    pub max_cache_bytes: u64,
+    // 没有精确匹配时退回同系列中最接近的 Minecraft 版本
+    pub nearest_version_fallback: bool,
+    // debug / info / warn / error
+    pub log_level: String,
+    // text / json（每行一个 JSON 对象）
+    pub log_format: String,
+    // 仅在系统钥匙串不可用时使用，单独保存在 cf_api_key 文件中，不随设置返回给前端；
+    // 仍可从旧版 settings.json 读入
+    #[serde(skip_serializing)]
+    cf_api_key: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_mc_version: None,
+            default_loader: None,
+            default_source: None,
+            release_channel: "alpha".to_string(),
+            concurrency: 4,
+            offline: false,
+            max_cache_bytes: 256 * 1024 * 1024,
+            nearest_version_fallback: false,
+            log_level: "info".to_string(),
+            log_format: "text".to_string(),
+            cf_api_key: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SettingsPatch {
+    pub default_mc_version: Option<String>,
+    pub default_loader: Option<String>,
+    pub default_source: Option<String>,
+    pub release_channel: Option<String>,
+    pub concurrency: Option<usize>,
+    pub offline: Option<bool>,
+    pub max_cache_bytes: Option<u64>,
+    pub nearest_version_fallback: Option<bool>,
+    pub log_level: Option<String>,
+    pub log_format: Option<String>,
+}
+
+impl Settings {
+    pub fn max_release_level(&self) -> u8 {
+        match self.release_channel.to_lowercase().as_str() {
+            "release" => 1,
+            "beta" => 2,
+            _ => 3,
+        }
+    }
+}
+
+fn settings_path() -> PathBuf {
+    let dir = app_data_dir();
+    let _ = std::fs::create_dir_all(&dir);
+    dir.join("settings.json")
+}
+
+fn key_path() -> PathBuf {
+    settings_path().with_file_name("cf_api_key")
+}
+
+fn load() -> Settings {
+    let path = settings_path();
+    let mut settings: Settings = match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            log_event("warn", &format!("settings_parse_failed {}", e));
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    };
+    match std::fs::read_to_string(key_path()) {
+        Ok(key) => settings.cf_api_key = Some(key.trim().to_string()).filter(|k| !k.is_empty()),
+        // 旧版把 Key 写在 settings.json 中，迁移到单独的文件
+        Err(_) if settings.cf_api_key.is_some() => {
+            if let Err(e) = save_key(settings.cf_api_key.as_deref()).and_then(|_| save(&settings)) {
+                log_event("warn", &format!("cf_api_key_migrate_failed {}", e));
+            }
+        }
+        Err(_) => {}
+    }
+    settings
+}
+
+fn save_key(key: Option<&str>) -> anyhow::Result<()> {
+    let path = key_path();
+    match key {
+        Some(k) => std::fs::write(&path, k).context("Failed to write API key"),
+        None if path.exists() => std::fs::remove_file(&path).context("Failed to remove API key"),
+        None => Ok(()),
+    }
+}
+
+fn save(settings: &Settings) -> anyhow::Result<()> {
+    let path = settings_path();
+    let text = serde_json::to_string_pretty(settings)?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, text).context("Failed to write settings")?;
+    std::fs::rename(&tmp, &path).context("Failed to write settings")?;
+    Ok(())
+}
+
+pub fn current() -> Settings {
+    SETTINGS.read().unwrap().clone()
+}
+
+pub fn apply_on_startup() {
+    let s = current();
+    crate::logging::configure(&s.log_level, &s.log_format);
+    crate::util::set_offline(s.offline);
+    crate::cache::set_max_cache_bytes(s.max_cache_bytes);
+}
+
+pub fn update(patch: SettingsPatch) -> anyhow::Result<Settings> {
+    let mut w = SETTINGS.write().unwrap();
+    let mut next = w.clone();
+    if let Some(v) = patch.default_mc_version {
+        next.default_mc_version = Some(v).filter(|s| !s.trim().is_empty());
+    }
+    if let Some(v) = patch.default_loader {
+        next.default_loader = Some(v).filter(|s| !s.trim().is_empty());
+    }
+    if let Some(v) = patch.default_source {
+        next.default_source = Some(v).filter(|s| !s.trim().is_empty());
+    }
+    if let Some(v) = patch.release_channel {
+        let v = v.to_lowercase();
+        if !["release", "beta", "alpha"].contains(&v.as_str()) {
+            return Err(anyhow!("Unknown release channel: {}", v));
+        }
+        next.release_channel = v;
+    }
+    if let Some(v) = patch.concurrency {
+        if v == 0 {
+            return Err(anyhow!("Concurrency must be at least 1"));
+        }
+        next.concurrency = v;
+    }
+    if let Some(v) = patch.offline {
+        next.offline = v;
+    }
+    if let Some(v) = patch.max_cache_bytes {
+        next.max_cache_bytes = v;
+    }
+    if let Some(v) = patch.nearest_version_fallback {
+        next.nearest_version_fallback = v;
+    }
+    if let Some(v) = patch.log_level {
+        let v = v.to_lowercase();
+        if !crate::logging::LEVELS.contains(&v.as_str()) {
+            return Err(anyhow!("Unknown log level: {}", v));
+        }
+        next.log_level = v;
+    }
+    if let Some(v) = patch.log_format {
+        let v = v.to_lowercase();
+        if !crate::logging::FORMATS.contains(&v.as_str()) {
+            return Err(anyhow!("Unknown log format: {}", v));
+        }
+        next.log_format = v;
+    }
+    save(&next)?;
+    *w = next.clone();
+    drop(w);
+    crate::logging::configure(&next.log_level, &next.log_format);
+    crate::util::set_offline(next.offline);
+    crate::cache::set_max_cache_bytes(next.max_cache_bytes);
+    Ok(next)
+}
+
+pub fn stored_cf_api_key() -> Option<String> {
+    let from_keyring = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|e| e.get_password())
+        .ok();
+    from_keyring
+        .or_else(|| SETTINGS.read().unwrap().cf_api_key.clone())
+        .filter(|k| !k.trim().is_empty())
+}
+
+pub fn cf_api_key_storage() -> Option<&'static str> {
+    if keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|e| e.get_password())
+        .is_ok()
+    {
+        Some("keyring")
+    } else if SETTINGS.read().unwrap().cf_api_key.is_some() {
+        Some("file")
+    } else {
+        None
+    }
+}
+
+pub fn set_cf_api_key(key: Option<String>) -> anyhow::Result<&'static str> {
+    let key = key.filter(|k| !k.trim().is_empty());
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER);
+    let mut w = SETTINGS.write().unwrap();
+    let mut next = w.clone();
+    let storage = match &key {
+        Some(k) => match entry.and_then(|e| e.set_password(k)) {
+            Ok(()) => {
+                next.cf_api_key = None;
+                "keyring"
+            }
+            Err(e) => {
+                log_event("warn", &format!("keyring_unavailable {}", e));
+                next.cf_api_key = Some(k.clone());
+                "file"
+            }
+        },
+        None => {
+            if let Ok(e) = entry {
+                let _ = e.delete_credential();
+            }
+            next.cf_api_key = None;
+            "none"
+        }
+    };
+    save_key(next.cf_api_key.as_deref())?;
+    save(&next)?;
+    *w = next;
+    Ok(storage)
+}
+
+pub fn resolve_default(
+    value: Option<String>,
+    default: Option<String>,
+    what: &str,
+) -> anyhow::Result<String> {
+    value
+        .filter(|v| !v.trim().is_empty())
+        .or(default)
+        .ok_or_else(|| anyhow!("No {} given and no default configured in settings", what))
+}
+
+pub fn resolve_mc_version(value: Option<String>) -> anyhow::Result<String> {
+    resolve_default(value, current().default_mc_version, "Minecraft version")
+}
+
+pub fn resolve_loader(value: Option<String>) -> anyhow::Result<String> {
+    resolve_default(value, current().default_loader, "loader")
+}
+
+pub fn resolve_source(value: Option<String>) -> anyhow::Result<String> {
+    resolve_default(value, current().default_source, "source")
+}
@@ -11,7 +11,9 @@ pub fn app_data_dir() -> PathBuf {
 
 use anyhow::anyhow;
 use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::time::{sleep, Duration};
+static OFFLINE: AtomicBool = AtomicBool::new(false);
 static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
     reqwest::Client::builder()
         .user_agent("ModDependencyUpdater/1.0 (Tauri)")
@@ -21,9 +23,23 @@ static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
         .expect("http client")
 });
 pub fn http_client() -> Result<reqwest::Client> {
+    if is_offline() {
+        return Err(anyhow!(
+            "Offline mode is enabled, network access is disabled"
+        ));
+    }
     Ok(CLIENT.clone())
 }
 
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub fn set_offline(enabled: bool) {
+    OFFLINE.store(enabled, Ordering::Relaxed);
+    log_event("info", &format!("offline_mode {}", enabled));
+}
+
 pub async fn send_with_retry(
     rb: reqwest::RequestBuilder,
     retries: usize,
@@ -46,22 +62,7 @@ pub async fn send_with_retry(
     Err(anyhow!(last_err.unwrap()))
 }
 
-pub fn log_event(level: &str, msg: &str) {
-    let dir = app_data_dir().join("logs");
-    let _ = std::fs::create_dir_all(&dir);
-    let path = dir.join("runtime.log");
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_millis())
-        .unwrap_or(0);
-    let line = format!("[{}][{}] {}\n", ts, level, msg);
-    let _ = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&path)
-        .and_then(|mut f| std::io::Write::write_all(&mut f, line.as_bytes()));
-    println!("{}", line.trim_end());
-}
+pub use crate::logging::log_event;
 
 pub fn shorten(s: &str, max: usize) -> String {
     if s.len() <= max {
@@ -72,17 +73,17 @@ pub fn shorten(s: &str, max: usize) -> String {
 }
 
 pub fn resolve_cf_api_key(cf_api_key: Option<String>) -> anyhow::Result<String> {
-    let api_key = if let Some(key) = cf_api_key {
-        if key.trim().is_empty() {
-            std::env::var("CF_API_KEY").ok()
-        } else {
-            Some(key)
-        }
-    } else {
-        std::env::var("CF_API_KEY").ok()
-    };
-    api_key
-        .ok_or_else(|| anyhow::anyhow!("CF_API_KEY is required for CurseForge (Input or Env Var)"))
+    if is_offline() {
+        // 离线模式只读缓存，不需要 API Key
+        return Ok(cf_api_key.unwrap_or_default());
+    }
+    let api_key = cf_api_key
+        .filter(|key| !key.trim().is_empty())
+        .or_else(crate::settings::stored_cf_api_key)
+        .or_else(|| std::env::var("CF_API_KEY").ok());
+    api_key.ok_or_else(|| {
+        anyhow::anyhow!("CF_API_KEY is required for CurseForge (Input, Settings or Env Var)")
+    })
 }
 
 pub fn loader_name_to_tag(name: &str) -> String {
@@ -111,8 +112,7 @@ pub fn release_type_str(code: u8) -> &'static str {
 }
 
 pub async fn cache_icon_from_url(source: &str, key: &str, url: &str) -> anyhow::Result<String> {
-    let dir = app_data_dir().join("icons");
-    let _ = std::fs::create_dir_all(&dir);
+    let dir = crate::cache::icons_dir();
     let lower = url.to_lowercase();
     let ext = if lower.ends_with(".png") {
         "png"
@@ -135,7 +135,7 @@ pub async fn cache_icon_from_url(source: &str, key: &str, url: &str) -> anyhow::
             }
         }
     }
-    if path.exists() && use_cached {
+    if path.exists() && (use_cached || is_offline()) {
         log_event(
             "info",
             &format!(
@@ -147,6 +147,13 @@ pub async fn cache_icon_from_url(source: &str, key: &str, url: &str) -> anyhow::
         );
         return Ok(path.to_string_lossy().into());
     }
+    if is_offline() {
+        return Err(anyhow!(
+            "Offline mode: icon {} {} was never cached",
+            source,
+            key
+        ));
+    }
     let client = http_client()?;
     let mut last_err: Option<anyhow::Error> = None;
     for attempt in 0..3 {
@@ -156,6 +163,7 @@ pub async fn cache_icon_from_url(source: &str, key: &str, url: &str) -> anyhow::
                     if let Err(e) = std::fs::write(&path, &bytes) {
                         last_err = Some(anyhow::anyhow!(e));
                     } else {
+                        crate::cache::enforce_size_limit();
                         log_event(
                             "info",
                             &format!(